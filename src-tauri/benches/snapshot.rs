@@ -0,0 +1,50 @@
+//! Reads of the stored tasks, which should not depend on their number
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::sync::Arc;
+use tokio_display_lib::bench::{Snapshot, Task, Tasks};
+use uuid::Uuid;
+
+fn tasks(count: u64) -> Tasks {
+    let app_id = Uuid::new_v4();
+    (0..count)
+        .map(|id| {
+            let task = Task {
+                app_id,
+                id,
+                tid: Some(id),
+                name: Some(format!("task-{id}")),
+                kind: Some("spawn".to_owned()),
+                location: Some("src/main.rs:10:5".to_owned()),
+                stats: Default::default(),
+            };
+            (task.id(), Arc::new(task))
+        })
+        .collect()
+}
+
+fn reads(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tasks");
+    for count in [1_000, 10_000, 100_000] {
+        let snapshot = Snapshot::in_memory(tasks(count));
+        let key = format!(
+            "{}.{}",
+            snapshot.read().values().next().unwrap().app_id,
+            count / 2
+        );
+
+        group.bench_with_input(BenchmarkId::new("read", count), &snapshot, |b, snapshot| {
+            b.iter(|| black_box(snapshot.read()))
+        });
+        group.bench_with_input(BenchmarkId::new("copy", count), &snapshot, |b, snapshot| {
+            b.iter(|| black_box(Tasks::clone(&snapshot.read())))
+        });
+        group.bench_with_input(BenchmarkId::new("get", count), &snapshot, |b, snapshot| {
+            b.iter(|| black_box(snapshot.read().get(&key).cloned()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, reads);
+criterion_main!(benches);
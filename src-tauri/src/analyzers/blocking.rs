@@ -0,0 +1,236 @@
+use crate::domain::{PollSpan, Task, TaskHistograms, TaskState};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use uuid::Uuid;
+
+/// Incidents kept for every application, the oldest are discarded first
+const MAX_INCIDENTS: usize = 500;
+
+/// Tasks returned in the worst offenders of an application
+const MAX_OFFENDERS: usize = 20;
+
+/// A poll which lasted longer than the budget, blocking its worker thread
+#[derive(Serialize, Clone, Debug)]
+pub struct BlockingIncident {
+    pub app_id: Uuid,
+    pub task_id: u64,
+    pub name: Option<String>,
+    pub location: Option<String>,
+    /// When the poll started, in the application's clock
+    pub started_at: SystemTime,
+    pub duration: Duration,
+    /// The poll had not ended yet when it was reported, its duration is the
+    /// time it ran so far
+    pub ongoing: bool,
+}
+
+/// Task blocking its worker thread the most
+#[derive(Serialize, Clone, Debug)]
+pub struct BlockingOffender {
+    pub task_id: u64,
+    pub name: Option<String>,
+    pub location: Option<String>,
+    /// Polls exceeding the budget, from the incidents or the poll times histogram
+    pub polls_over_budget: u64,
+    pub longest_poll: Duration,
+    /// Time the observed incidents blocked the worker threads
+    pub blocked_time: Duration,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct BlockingReport {
+    /// Latest incidents, oldest first
+    pub incidents: Vec<BlockingIncident>,
+    /// Tasks which blocked the longest first
+    pub worst_offenders: Vec<BlockingOffender>,
+}
+
+#[derive(Default)]
+struct AppBlocking {
+    incidents: VecDeque<BlockingIncident>,
+    offenders: HashMap<u64, BlockingOffender>,
+    // End of the latest poll already checked, for every task
+    checked_until: HashMap<u64, SystemTime>,
+    // Start of the running poll already reported, for every task
+    reported_running: HashMap<u64, SystemTime>,
+}
+
+impl AppBlocking {
+    fn push(&mut self, incident: BlockingIncident) {
+        if self.incidents.len() == MAX_INCIDENTS {
+            self.incidents.pop_front();
+        }
+        self.incidents.push_back(incident);
+    }
+
+    fn offender(&mut self, task: &Task) -> &mut BlockingOffender {
+        self.offenders
+            .entry(task.id)
+            .or_insert_with(|| BlockingOffender {
+                task_id: task.id,
+                name: task.name.clone(),
+                location: task.location.clone(),
+                polls_over_budget: 0,
+                longest_poll: Duration::ZERO,
+                blocked_time: Duration::ZERO,
+            })
+    }
+}
+
+/// Looks for the polls exceeding a budget, which starve the other tasks
+/// of their worker thread
+///
+/// Only the last poll between two updates of a task is observed, the poll
+/// times histograms of the watched tasks complete the offenders with the
+/// polls that were missed.
+#[derive(Default)]
+pub(crate) struct BlockingDetector {
+    applications: Mutex<HashMap<Uuid, AppBlocking>>,
+}
+
+impl BlockingDetector {
+    /// Records the polls of an application exceeding the budget, returns the
+    /// new incidents
+    ///
+    /// `now` is the time of the last update received from the application,
+    /// in the application's clock. `tasks` are all the stored tasks, the
+    /// offenders which were removed are forgotten.
+    pub fn analyze(
+        &self,
+        app_id: Uuid,
+        now: SystemTime,
+        tasks: &[Arc<Task>],
+        poll_spans: &HashMap<u64, Vec<PollSpan>>,
+        histograms: &HashMap<u64, Arc<TaskHistograms>>,
+        budget: Duration,
+    ) -> Vec<BlockingIncident> {
+        let mut applications = self.applications.lock().unwrap();
+        let app = applications.entry(app_id).or_default();
+
+        let mut new_incidents = Vec::new();
+        for task in tasks.iter().filter(|task| task.app_id == app_id) {
+            let checked_until = app.checked_until.get(&task.id).copied();
+            let spans = poll_spans
+                .get(&task.id)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            for span in spans.iter().filter(|span| Some(span.ended) > checked_until) {
+                let duration = span.ended.duration_since(span.started).unwrap_or_default();
+                if duration <= budget {
+                    continue;
+                }
+                let offender = app.offender(task);
+                offender.longest_poll = offender.longest_poll.max(duration);
+                offender.blocked_time += duration;
+
+                // The poll was already reported while running
+                if let Some(incident) = app.incidents.iter_mut().find(|incident| {
+                    incident.ongoing
+                        && incident.task_id == task.id
+                        && incident.started_at == span.started
+                }) {
+                    incident.ongoing = false;
+                    incident.duration = duration;
+                    continue;
+                }
+                app.offender(task).polls_over_budget += 1;
+                let incident = BlockingIncident {
+                    app_id,
+                    task_id: task.id,
+                    name: task.name.clone(),
+                    location: task.location.clone(),
+                    started_at: span.started,
+                    duration,
+                    ongoing: false,
+                };
+                new_incidents.push(incident.clone());
+                app.push(incident);
+            }
+            if let Some(last) = spans.last() {
+                app.checked_until.insert(task.id, last.ended);
+            }
+
+            // Poll still running past the budget
+            if task.state() == TaskState::Running {
+                if let Some(started) = task.stats.last_poll_started {
+                    let duration = now.duration_since(started).unwrap_or_default();
+                    // Also reported once its incident was discarded
+                    let reported = app.reported_running.get(&task.id) == Some(&started);
+                    if duration > budget && !reported {
+                        app.reported_running.insert(task.id, started);
+                        app.offender(task).polls_over_budget += 1;
+                        let incident = BlockingIncident {
+                            app_id,
+                            task_id: task.id,
+                            name: task.name.clone(),
+                            location: task.location.clone(),
+                            started_at: started,
+                            duration,
+                            ongoing: true,
+                        };
+                        new_incidents.push(incident.clone());
+                        app.push(incident);
+                    }
+                }
+            }
+
+            // Polls missed between two updates
+            if let Some(poll_times) = histograms
+                .get(&task.id)
+                .and_then(|histograms| histograms.poll_times.as_ref())
+            {
+                let over_budget = poll_times.count_above(budget);
+                if over_budget > 0 {
+                    let offender = app.offender(task);
+                    offender.polls_over_budget = offender.polls_over_budget.max(over_budget);
+                    offender.longest_poll = offender.longest_poll.max(poll_times.max);
+                }
+            }
+        }
+        // Spans of the completed tasks are removed
+        app.checked_until
+            .retain(|task_id, _| poll_spans.contains_key(task_id));
+        let stored: HashSet<u64> = tasks
+            .iter()
+            .filter(|task| task.app_id == app_id)
+            .map(|task| task.id)
+            .collect();
+        app.reported_running
+            .retain(|task_id, _| stored.contains(task_id));
+        app.offenders.retain(|task_id, _| stored.contains(task_id));
+
+        new_incidents
+    }
+
+    /// Returns the incidents and the worst offenders of an application
+    pub fn report(&self, app_id: Uuid) -> BlockingReport {
+        let applications = self.applications.lock().unwrap();
+        let Some(app) = applications.get(&app_id) else {
+            return BlockingReport::default();
+        };
+
+        let mut worst_offenders: Vec<BlockingOffender> = app.offenders.values().cloned().collect();
+        worst_offenders.sort_by(|left, right| {
+            (
+                right.blocked_time,
+                right.longest_poll,
+                right.polls_over_budget,
+            )
+                .cmp(&(left.blocked_time, left.longest_poll, left.polls_over_budget))
+        });
+        worst_offenders.truncate(MAX_OFFENDERS);
+
+        BlockingReport {
+            incidents: app.incidents.iter().cloned().collect(),
+            worst_offenders,
+        }
+    }
+
+    pub fn forget(&self, app_id: Uuid) {
+        self.applications.lock().unwrap().remove(&app_id);
+    }
+}
@@ -0,0 +1,226 @@
+use crate::domain::{AsyncOp, Resource, Task};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use uuid::Uuid;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub enum DeadlockKind {
+    /// Tasks waiting on resources held by each other
+    Cycle,
+    /// Task waiting on a resource for longer than the configured threshold
+    LongWait,
+}
+
+/// A task waiting for an async op on a resource to become ready
+#[derive(Serialize, Clone, Debug)]
+pub struct WaitingTask {
+    pub task_id: u64,
+    pub name: Option<String>,
+    pub location: Option<String>,
+    pub async_op_id: u64,
+    pub async_op: String,
+    pub resource_id: u64,
+    pub resource: String,
+    pub resource_location: Option<String>,
+    pub holder_task_id: Option<u64>,
+    pub waiting_for: Duration,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DeadlockReport {
+    pub app_id: Uuid,
+    pub kind: DeadlockKind,
+    pub tasks: Vec<WaitingTask>,
+}
+
+impl DeadlockReport {
+    /// Identifies the report, so that the same suspected deadlock
+    /// is reported only once while it lasts
+    fn signature(&self) -> String {
+        let mut waits = self
+            .tasks
+            .iter()
+            .map(|task| format!("{}:{}", task.task_id, task.async_op_id))
+            .collect::<Vec<_>>();
+        waits.sort();
+        format!("{}.{:?}.{}", self.app_id, self.kind, waits.join(","))
+    }
+}
+
+/// Builds a wait-for graph out of the tasks, resources and async ops of an
+/// application and reports suspected deadlocks
+///
+/// An edge goes from a task to another one if the first task awaits an async
+/// op on a resource which is held by the second task. As console-api does not
+/// report resource ownership, the holder is the last task that had an async op
+/// on the resource complete while the resource is still acquired.
+pub(crate) struct DeadlockDetector {
+    long_wait_threshold: Duration,
+    reported: Mutex<HashSet<String>>,
+}
+
+impl DeadlockDetector {
+    pub const DEFAULT_LONG_WAIT_THRESHOLD: Duration = Duration::from_secs(10);
+
+    pub fn new(long_wait_threshold: Duration) -> Self {
+        Self {
+            long_wait_threshold,
+            reported: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Returns all the suspected deadlocks of an application
+    ///
+    /// `now` is the time of the last update received from the application,
+    /// in the application's clock
+    pub fn analyze(
+        &self,
+        app_id: Uuid,
+        now: SystemTime,
+        tasks: &[Arc<Task>],
+        resources: &[Arc<Resource>],
+        async_ops: &[Arc<AsyncOp>],
+    ) -> Vec<DeadlockReport> {
+        let tasks: HashMap<u64, &Task> = tasks
+            .iter()
+            .filter(|task| task.app_id == app_id)
+            .map(|task| (task.id, task.as_ref()))
+            .collect();
+        let resources: HashMap<u64, &Resource> = resources
+            .iter()
+            .filter(|resource| resource.app_id == app_id)
+            .map(|resource| (resource.id, resource.as_ref()))
+            .collect();
+
+        let waits: Vec<WaitingTask> = async_ops
+            .iter()
+            .filter(|async_op| async_op.app_id == app_id)
+            .filter_map(|async_op| {
+                let task_id = async_op.task_id?;
+                let resource = resources.get(&async_op.resource_id?)?;
+                let pending_since = async_op.pending_since?;
+                let task = tasks.get(&task_id);
+
+                Some(WaitingTask {
+                    task_id,
+                    name: task.and_then(|task| task.name.clone()),
+                    location: task.and_then(|task| task.location.clone()),
+                    async_op_id: async_op.id,
+                    async_op: async_op.source.clone(),
+                    resource_id: resource.id,
+                    resource: resource.concrete_type.clone(),
+                    resource_location: resource.location.clone(),
+                    holder_task_id: resource
+                        .is_held()
+                        .then_some(resource.holder_task_id)
+                        .flatten()
+                        .filter(|holder| *holder != task_id),
+                    waiting_for: now.duration_since(pending_since).unwrap_or_default(),
+                })
+            })
+            .collect();
+
+        // Wait-for graph, every task points to the tasks holding
+        // the resources it waits for
+        let mut graph: HashMap<u64, Vec<(u64, usize)>> = HashMap::new();
+        for (index, wait) in waits.iter().enumerate() {
+            if let Some(holder) = wait.holder_task_id {
+                graph.entry(wait.task_id).or_default().push((holder, index));
+            }
+        }
+
+        let mut reports = Vec::new();
+        let mut visited = HashSet::new();
+        let mut nodes: Vec<u64> = graph.keys().copied().collect();
+        nodes.sort();
+        for node in nodes {
+            if !visited.contains(&node) {
+                let mut cycles = Vec::new();
+                find_cycles(
+                    node,
+                    &graph,
+                    &mut Vec::new(),
+                    &mut HashSet::new(),
+                    &mut visited,
+                    &mut cycles,
+                );
+                reports.extend(cycles.into_iter().map(|cycle| {
+                    DeadlockReport {
+                        app_id,
+                        kind: DeadlockKind::Cycle,
+                        tasks: cycle
+                            .into_iter()
+                            .map(|index| waits[index].clone())
+                            .collect(),
+                    }
+                }));
+            }
+        }
+
+        reports.extend(
+            waits
+                .into_iter()
+                .filter(|wait| wait.waiting_for >= self.long_wait_threshold)
+                .map(|wait| DeadlockReport {
+                    app_id,
+                    kind: DeadlockKind::LongWait,
+                    tasks: vec![wait],
+                }),
+        );
+
+        reports
+    }
+
+    /// Keeps only the reports that were not returned by the previous call,
+    /// suspected deadlocks that disappeared will be reported again if they
+    /// show up later
+    pub fn retain_new(&self, reports: Vec<DeadlockReport>) -> Vec<DeadlockReport> {
+        let signatures: HashSet<String> = reports.iter().map(DeadlockReport::signature).collect();
+        let mut reported = self.reported.lock().unwrap();
+        let reports = reports
+            .into_iter()
+            .filter(|report| !reported.contains(&report.signature()))
+            .collect();
+        *reported = signatures;
+
+        reports
+    }
+}
+
+impl Default for DeadlockDetector {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_LONG_WAIT_THRESHOLD)
+    }
+}
+
+/// Depth first search collecting the cycles reachable from `node`,
+/// every cycle is returned as the list of the waits forming it
+fn find_cycles(
+    node: u64,
+    graph: &HashMap<u64, Vec<(u64, usize)>>,
+    path: &mut Vec<(u64, usize)>,
+    on_path: &mut HashSet<u64>,
+    visited: &mut HashSet<u64>,
+    cycles: &mut Vec<Vec<usize>>,
+) {
+    visited.insert(node);
+    on_path.insert(node);
+
+    for &(next, wait) in graph.get(&node).into_iter().flatten() {
+        path.push((node, wait));
+        if on_path.contains(&next) {
+            if let Some(start) = path.iter().position(|(task, _)| *task == next) {
+                cycles.push(path[start..].iter().map(|(_, wait)| *wait).collect());
+            }
+        } else if !visited.contains(&next) {
+            find_cycles(next, graph, path, on_path, visited, cycles);
+        }
+        path.pop();
+    }
+
+    on_path.remove(&node);
+}
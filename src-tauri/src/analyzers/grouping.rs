@@ -0,0 +1,339 @@
+use crate::domain::Task;
+use crate::error::Error as TraceError;
+use crate::query::{FieldValue, TaskField};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+use uuid::Uuid;
+
+/// Groupings whose previous totals are kept to compute the trends
+const MAX_TRACKED_GROUPINGS: usize = 16;
+
+/// Attribute the tasks are grouped by
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "by", rename_all = "snake_case")]
+pub enum GroupBy {
+    /// Source location the tasks were spawned at
+    Location,
+    Kind,
+    /// Name of the tasks, the tasks matching one of the patterns are grouped
+    /// under it, eg: `worker-*`
+    Name {
+        #[serde(default)]
+        patterns: Vec<String>,
+    },
+    /// Any field of the task query, eg: `state`
+    Field {
+        field: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TaskGrouping {
+    #[serde(flatten)]
+    pub by: GroupBy,
+    /// Only groups the tasks of this application if set
+    #[serde(default)]
+    pub app_id: Option<Uuid>,
+}
+
+impl Default for TaskGrouping {
+    fn default() -> Self {
+        Self {
+            by: GroupBy::Location,
+            app_id: None,
+        }
+    }
+}
+
+/// Distribution of a duration over the tasks of a group
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct DurationSummary {
+    pub total: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+/// Change of a group since it was previously computed with the same grouping
+#[derive(Serialize, Clone, Debug)]
+pub struct GroupTrend {
+    /// Time since the previous computation
+    pub period: Duration,
+    pub live_change: i64,
+    pub spawned_per_sec: f64,
+    /// Busy time added per second, ie: the number of workers kept busy on average
+    pub busy_ratio: f64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TaskGroup {
+    /// Value the tasks share, none for the tasks without one
+    pub key: Option<String>,
+    pub count: usize,
+    pub live: usize,
+    pub completed: usize,
+    pub busy: DurationSummary,
+    /// Time the tasks were neither polled nor scheduled
+    pub idle: DurationSummary,
+    /// None the first time the grouping is computed
+    pub trend: Option<GroupTrend>,
+}
+
+#[derive(Clone, Copy)]
+struct GroupTotals {
+    live: usize,
+    busy: Duration,
+}
+
+struct Computation {
+    at: Instant,
+    groups: HashMap<Option<String>, GroupTotals>,
+}
+
+/// Aggregates the tasks into groups, keeping the totals of the latest
+/// groupings so that the next ones report how the groups evolve
+#[derive(Default)]
+pub(crate) struct TaskGrouper {
+    previous: Mutex<HashMap<String, Computation>>,
+}
+
+enum Key {
+    Location,
+    Kind,
+    Name(Vec<String>),
+    Field(TaskField),
+}
+
+impl Key {
+    fn parse(by: &GroupBy) -> Result<Self, TraceError> {
+        Ok(match by {
+            GroupBy::Location => Key::Location,
+            GroupBy::Kind => Key::Kind,
+            GroupBy::Name { patterns } => Key::Name(patterns.clone()),
+            GroupBy::Field { field } => Key::Field(
+                TaskField::parse(field)
+                    .ok_or_else(|| TraceError::InvalidQuery(format!("unknown field {field}")))?,
+            ),
+        })
+    }
+
+    fn of(&self, task: &Task) -> Option<String> {
+        match self {
+            Key::Location => task.location.clone(),
+            Key::Kind => task.kind.clone(),
+            Key::Name(patterns) => {
+                let name = task.name.as_deref()?;
+                Some(
+                    patterns
+                        .iter()
+                        .find(|pattern| matches_pattern(pattern, name))
+                        .cloned()
+                        .unwrap_or_else(|| name.to_owned()),
+                )
+            }
+            Key::Field(field) => match field.value(task) {
+                FieldValue::Text(text) => text,
+                FieldValue::Number(number) => number.map(|number| number.to_string()),
+            },
+        }
+    }
+}
+
+impl TaskGrouper {
+    /// Checks the grouping can be computed
+    pub fn validate(grouping: &TaskGrouping) -> Result<(), TraceError> {
+        Key::parse(&grouping.by).map(|_| ())
+    }
+
+    /// Groups the tasks, largest groups first
+    ///
+    /// `now` is the current time of every application, in its own clock
+    ///
+    /// # Error
+    ///
+    /// If the grouping field is unknown, an error is returned
+    pub fn group(
+        &self,
+        grouping: &TaskGrouping,
+        tasks: &[Arc<Task>],
+        now: &HashMap<Uuid, SystemTime>,
+    ) -> Result<Vec<TaskGroup>, TraceError> {
+        let key = Key::parse(&grouping.by)?;
+
+        let mut members: HashMap<Option<String>, Vec<&Task>> = HashMap::new();
+        for task in tasks {
+            if grouping.app_id.is_some_and(|app_id| app_id != task.app_id) {
+                continue;
+            }
+            members.entry(key.of(task)).or_default().push(task);
+        }
+
+        let at = Instant::now();
+        let mut previous = self.previous.lock().unwrap();
+        let signature = serde_json::to_string(grouping)?;
+        let last = previous.get(&signature);
+
+        let mut groups: Vec<TaskGroup> = members
+            .into_iter()
+            .map(|(key, tasks)| {
+                let mut group = summarize(key, &tasks, now);
+                if let Some(last) = last {
+                    let period = at.duration_since(last.at);
+                    group.trend = Some(trend(
+                        &group,
+                        last.groups.get(&group.key),
+                        &tasks,
+                        now,
+                        period,
+                    ));
+                }
+                group
+            })
+            .collect();
+        groups.sort_by(|left, right| right.count.cmp(&left.count).then(left.key.cmp(&right.key)));
+
+        if !previous.contains_key(&signature) && previous.len() >= MAX_TRACKED_GROUPINGS {
+            let oldest = previous
+                .iter()
+                .min_by_key(|(_, computation)| computation.at)
+                .map(|(signature, _)| signature.clone());
+            if let Some(oldest) = oldest {
+                previous.remove(&oldest);
+            }
+        }
+        previous.insert(
+            signature,
+            Computation {
+                at,
+                groups: groups
+                    .iter()
+                    .map(|group| {
+                        let totals = GroupTotals {
+                            live: group.live,
+                            busy: group.busy.total,
+                        };
+                        (group.key.clone(), totals)
+                    })
+                    .collect(),
+            },
+        );
+
+        Ok(groups)
+    }
+}
+
+fn summarize(key: Option<String>, tasks: &[&Task], now: &HashMap<Uuid, SystemTime>) -> TaskGroup {
+    let completed = tasks
+        .iter()
+        .filter(|task| task.stats.dropped_at.is_some())
+        .count();
+
+    let busy = tasks.iter().map(|task| task.stats.busy_time).collect();
+    let idle = tasks
+        .iter()
+        .map(|task| {
+            let stats = &task.stats;
+            let end = stats.dropped_at.or_else(|| now.get(&task.app_id).copied());
+            let lifetime = stats
+                .created_at
+                .zip(end)
+                .and_then(|(created_at, end)| end.duration_since(created_at).ok())
+                .unwrap_or_default();
+            lifetime
+                .saturating_sub(stats.busy_time)
+                .saturating_sub(stats.scheduled_time)
+        })
+        .collect();
+
+    TaskGroup {
+        key,
+        count: tasks.len(),
+        live: tasks.len() - completed,
+        completed,
+        busy: summarize_durations(busy),
+        idle: summarize_durations(idle),
+        trend: None,
+    }
+}
+
+fn summarize_durations(mut durations: Vec<Duration>) -> DurationSummary {
+    if durations.is_empty() {
+        return DurationSummary::default();
+    }
+    durations.sort();
+    // Nearest rank
+    let percentile = |percentile: usize| {
+        let rank = (durations.len() * percentile).div_ceil(100).max(1);
+        durations[rank - 1]
+    };
+
+    DurationSummary {
+        total: durations.iter().sum(),
+        p50: percentile(50),
+        p90: percentile(90),
+        p99: percentile(99),
+        max: durations[durations.len() - 1],
+    }
+}
+
+fn trend(
+    group: &TaskGroup,
+    last: Option<&GroupTotals>,
+    tasks: &[&Task],
+    now: &HashMap<Uuid, SystemTime>,
+    period: Duration,
+) -> GroupTrend {
+    let last = last.copied().unwrap_or(GroupTotals {
+        live: 0,
+        busy: Duration::ZERO,
+    });
+    let spawned = tasks
+        .iter()
+        .filter(|task| {
+            let since = now
+                .get(&task.app_id)
+                .and_then(|now| now.checked_sub(period));
+            task.stats
+                .created_at
+                .zip(since)
+                .is_some_and(|(created_at, since)| created_at > since)
+        })
+        .count();
+
+    let seconds = period.as_secs_f64();
+    let per_second = |value: f64| if seconds > 0.0 { value / seconds } else { 0.0 };
+    GroupTrend {
+        period,
+        live_change: group.live as i64 - last.live as i64,
+        spawned_per_sec: per_second(spawned as f64),
+        // Completed tasks removed by the retention lower the total
+        busy_ratio: per_second(group.busy.total.saturating_sub(last.busy).as_secs_f64()),
+    }
+}
+
+/// Matches a name against a pattern where `*` stands for any characters
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcard
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
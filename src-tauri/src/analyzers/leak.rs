@@ -0,0 +1,229 @@
+use crate::domain::settings::LeakDetectionSettings;
+use crate::domain::{Task, TaskState};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use uuid::Uuid;
+
+/// Minimum delay between two samples of the live tasks of a location
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Samples needed before a growth is reported
+const MIN_GROWTH_SAMPLES: usize = 6;
+
+/// Task ids sent with a report
+const MAX_SAMPLE_TASKS: usize = 10;
+
+/// Live tasks of every spawn location of an application, over time
+type LocationSamples = HashMap<String, VecDeque<(SystemTime, usize)>>;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub enum LeakKind {
+    /// Live tasks of the location only increased over the window
+    Growth,
+    /// Live tasks of the location were not polled for longer than the threshold
+    LongIdle,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LeakReport {
+    pub app_id: Uuid,
+    pub kind: LeakKind,
+    /// Spawn location of the tasks
+    pub location: String,
+    pub live_tasks: usize,
+    /// Live tasks added per minute over the window
+    pub growth_per_minute: f64,
+    /// Live tasks idle for longer than the threshold
+    pub idle_tasks: usize,
+    /// Newest tasks for a growth, longest idle ones otherwise
+    pub task_ids: Vec<u64>,
+}
+
+impl LeakReport {
+    /// Identifies the report, so that a suspected leak is reported only once
+    /// while it lasts
+    fn signature(&self) -> String {
+        format!("{}.{:?}.{}", self.app_id, self.kind, self.location)
+    }
+}
+
+/// Follows the live tasks of every spawn location over time, and reports
+/// the locations whose tasks keep piling up or are never polled anymore
+#[derive(Default)]
+pub(crate) struct LeakDetector {
+    // Live tasks of every location, in the clock of the application
+    samples: Mutex<HashMap<Uuid, LocationSamples>>,
+    reported: Mutex<HashSet<String>>,
+    latest: Mutex<Vec<LeakReport>>,
+}
+
+impl LeakDetector {
+    /// Samples the live tasks of an application and returns its suspected leaks
+    ///
+    /// `now` is the time of the last update received from the application,
+    /// in the application's clock
+    pub fn analyze(
+        &self,
+        app_id: Uuid,
+        now: SystemTime,
+        tasks: &[Arc<Task>],
+        settings: &LeakDetectionSettings,
+    ) -> Vec<LeakReport> {
+        let window = Duration::from_secs(settings.window);
+        let idle_threshold = Duration::from_secs(settings.idle_threshold);
+
+        let mut locations: HashMap<&str, Vec<&Task>> = HashMap::new();
+        for task in tasks {
+            if task.app_id != app_id || task.stats.dropped_at.is_some() {
+                continue;
+            }
+            if let Some(location) = &task.location {
+                locations.entry(location).or_default().push(task);
+            }
+        }
+
+        let mut samples = self.samples.lock().unwrap();
+        let app_samples = samples.entry(app_id).or_default();
+        for (location, location_samples) in app_samples.iter_mut() {
+            // Locations without live task anymore
+            if !locations.contains_key(location.as_str()) {
+                record(location_samples, now, 0, window);
+            }
+        }
+        app_samples.retain(|_, location_samples| {
+            location_samples
+                .iter()
+                .any(|(_, live_tasks)| *live_tasks > 0)
+        });
+
+        let mut reports = Vec::new();
+        for (location, mut live) in locations {
+            let location_samples = app_samples.entry(location.to_owned()).or_default();
+            record(location_samples, now, live.len(), window);
+
+            if let Some(growth_per_minute) =
+                growth(location_samples, now, window, settings.min_growth)
+            {
+                live.sort_by_key(|task| std::cmp::Reverse(task.stats.created_at));
+                reports.push(LeakReport {
+                    app_id,
+                    kind: LeakKind::Growth,
+                    location: location.to_owned(),
+                    live_tasks: live.len(),
+                    growth_per_minute,
+                    idle_tasks: 0,
+                    task_ids: live
+                        .iter()
+                        .take(MAX_SAMPLE_TASKS)
+                        .map(|task| task.id)
+                        .collect(),
+                });
+            }
+
+            let mut idle: Vec<(Duration, u64)> = live
+                .iter()
+                .filter(|task| task.state() == TaskState::Idle)
+                .filter_map(|task| {
+                    let since = task.stats.last_poll_ended.or(task.stats.created_at)?;
+                    let idle_for = now.duration_since(since).ok()?;
+                    (idle_for > idle_threshold).then_some((idle_for, task.id))
+                })
+                .collect();
+            if !idle.is_empty() {
+                idle.sort_by(|left, right| right.cmp(left));
+                reports.push(LeakReport {
+                    app_id,
+                    kind: LeakKind::LongIdle,
+                    location: location.to_owned(),
+                    live_tasks: live.len(),
+                    growth_per_minute: 0.0,
+                    idle_tasks: idle.len(),
+                    task_ids: idle
+                        .iter()
+                        .take(MAX_SAMPLE_TASKS)
+                        .map(|(_, task_id)| *task_id)
+                        .collect(),
+                });
+            }
+        }
+        reports.sort_by(|left, right| left.location.cmp(&right.location));
+
+        reports
+    }
+
+    /// Keeps the suspected leaks of all the applications, returns the
+    /// ones that were not reported yet
+    pub fn retain_new(&self, reports: Vec<LeakReport>) -> Vec<LeakReport> {
+        let signatures: HashSet<String> = reports.iter().map(LeakReport::signature).collect();
+        let mut reported = self.reported.lock().unwrap();
+        let new_reports = reports
+            .iter()
+            .filter(|report| !reported.contains(&report.signature()))
+            .cloned()
+            .collect();
+        *reported = signatures;
+        *self.latest.lock().unwrap() = reports;
+
+        new_reports
+    }
+
+    /// Suspected leaks found by the latest analysis
+    pub fn latest(&self) -> Vec<LeakReport> {
+        self.latest.lock().unwrap().clone()
+    }
+
+    pub fn forget(&self, app_id: Uuid) {
+        self.samples.lock().unwrap().remove(&app_id);
+    }
+}
+
+fn record(
+    samples: &mut VecDeque<(SystemTime, usize)>,
+    now: SystemTime,
+    live_tasks: usize,
+    window: Duration,
+) {
+    let recent = samples
+        .back()
+        .is_some_and(|(at, _)| now.duration_since(*at).unwrap_or_default() < SAMPLE_INTERVAL);
+    if !recent {
+        samples.push_back((now, live_tasks));
+    }
+    while samples
+        .front()
+        .is_some_and(|(at, _)| now.duration_since(*at).unwrap_or_default() > window)
+    {
+        samples.pop_front();
+    }
+}
+
+/// Live tasks added per minute, if they never decreased over most of the
+/// window and increased by at least `min_growth`
+fn growth(
+    samples: &VecDeque<(SystemTime, usize)>,
+    now: SystemTime,
+    window: Duration,
+    min_growth: usize,
+) -> Option<f64> {
+    let (first_at, first) = *samples.front()?;
+    let (last_at, last) = *samples.back()?;
+    let covered = now.duration_since(first_at).unwrap_or_default();
+    if samples.len() < MIN_GROWTH_SAMPLES || covered < window / 2 {
+        return None;
+    }
+
+    let monotonic = samples
+        .iter()
+        .zip(samples.iter().skip(1))
+        .all(|((_, previous), (_, next))| next >= previous);
+    if !monotonic || last < first + min_growth.max(1) {
+        return None;
+    }
+
+    let minutes = last_at.duration_since(first_at).ok()?.as_secs_f64() / 60.0;
+    (minutes > 0.0).then(|| (last - first) as f64 / minutes)
+}
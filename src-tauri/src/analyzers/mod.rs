@@ -0,0 +1,8 @@
+//! Module containing the analyzers run over the collected state
+//! in order to detect suspicious runtime behaviours
+
+pub(crate) mod blocking;
+pub(crate) mod deadlock;
+pub(crate) mod grouping;
+pub(crate) mod leak;
+pub(crate) mod scheduling;
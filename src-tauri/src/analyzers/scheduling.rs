@@ -0,0 +1,150 @@
+use crate::domain::{DurationHistogram, Task, TaskHistograms};
+use crate::state_manager::history::MetricsSample;
+use serde::Serialize;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use uuid::Uuid;
+
+/// Mean scheduling latency from which the runtime is considered busy
+const ELEVATED_LATENCY: Duration = Duration::from_millis(1);
+
+/// Mean scheduling latency from which the runtime is considered overloaded
+const SATURATED_LATENCY: Duration = Duration::from_millis(10);
+
+/// Latest part of the metrics history the saturation is computed on
+pub const SATURATION_WINDOW: Duration = Duration::from_secs(10);
+
+/// Tasks returned in a report, the ones waiting the longest first
+const MAX_TASKS: usize = 50;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SaturationLevel {
+    #[default]
+    Normal,
+    Elevated,
+    Saturated,
+}
+
+/// Whether the runtime keeps up with the woken tasks
+///
+/// A high busy ratio with a low latency means the tasks are slow, a high
+/// latency means the workers cannot poll the woken tasks in time.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Saturation {
+    pub level: SaturationLevel,
+    /// Mean delay between a wake and the poll over the recent samples
+    pub recent_latency: Duration,
+    /// Tasks waiting for a worker on average over the recent samples
+    pub waiting_tasks: f64,
+    /// Workers kept busy on average over the recent samples
+    pub busy_workers: f64,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct LatencyPercentiles {
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TaskScheduling {
+    pub task_id: u64,
+    pub name: Option<String>,
+    pub location: Option<String>,
+    /// Scheduled time divided by the polls since the task was spawned
+    pub mean_latency: Duration,
+    /// From the scheduled times histogram, only for the watched tasks
+    pub percentiles: Option<LatencyPercentiles>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SchedulingReport {
+    pub app_id: Uuid,
+    pub saturation: Saturation,
+    /// Merged scheduled times histograms of the watched tasks
+    pub percentiles: Option<LatencyPercentiles>,
+    /// Live tasks waiting the longest to be polled first
+    pub tasks: Vec<TaskScheduling>,
+}
+
+/// Analyzes the delays between the wakes of the tasks of an application and
+/// their polls
+///
+/// `recent` are the latest samples of the application's metrics history.
+pub(crate) fn analyze(
+    app_id: Uuid,
+    tasks: &[Arc<Task>],
+    histograms: &HashMap<u64, Arc<TaskHistograms>>,
+    recent: &[MetricsSample],
+) -> SchedulingReport {
+    let mut task_reports: Vec<TaskScheduling> = tasks
+        .iter()
+        .filter(|task| task.app_id == app_id && task.stats.dropped_at.is_none())
+        .map(|task| TaskScheduling {
+            task_id: task.id,
+            name: task.name.clone(),
+            location: task.location.clone(),
+            mean_latency: if task.stats.polls == 0 {
+                Duration::ZERO
+            } else {
+                task.stats.scheduled_time.div_f64(task.stats.polls as f64)
+            },
+            percentiles: histograms
+                .get(&task.id)
+                .and_then(|histograms| percentiles(histograms.scheduled_times.as_ref()?)),
+        })
+        .collect();
+    task_reports.sort_by(|left, right| {
+        let worst = |task: &TaskScheduling| {
+            task.percentiles
+                .as_ref()
+                .map_or(task.mean_latency, |percentiles| percentiles.p99)
+        };
+        worst(right).cmp(&worst(left))
+    });
+    task_reports.truncate(MAX_TASKS);
+
+    let merged = TaskHistograms::merge(histograms.values().map(Arc::as_ref));
+
+    SchedulingReport {
+        app_id,
+        saturation: saturation(recent),
+        percentiles: merged.scheduled_times.as_ref().and_then(percentiles),
+        tasks: task_reports,
+    }
+}
+
+fn percentiles(histogram: &DurationHistogram) -> Option<LatencyPercentiles> {
+    (histogram.count > 0).then_some(LatencyPercentiles {
+        p50: histogram.p50,
+        p90: histogram.p90,
+        p99: histogram.p99,
+        max: histogram.max,
+    })
+}
+
+fn saturation(recent: &[MetricsSample]) -> Saturation {
+    if recent.is_empty() {
+        return Saturation::default();
+    }
+
+    let count = recent.len() as f64;
+    let mean = |value: fn(&MetricsSample) -> f64| recent.iter().map(value).sum::<f64>() / count;
+    let recent_latency =
+        Duration::from_secs_f64(mean(|sample| sample.metrics.scheduling_latency).max(0.0));
+
+    Saturation {
+        level: if recent_latency >= SATURATED_LATENCY {
+            SaturationLevel::Saturated
+        } else if recent_latency >= ELEVATED_LATENCY {
+            SaturationLevel::Elevated
+        } else {
+            SaturationLevel::Normal
+        },
+        recent_latency,
+        waiting_tasks: mean(|sample| sample.metrics.scheduled_ratio),
+        busy_workers: mean(|sample| sample.metrics.busy_ratio),
+    }
+}
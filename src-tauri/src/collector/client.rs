@@ -0,0 +1,164 @@
+use super::{read_frame, write_frame, Frame, Message, Request};
+use crate::error::{Error as TraceError, ErrorReport};
+use crate::state_manager::StateManager;
+use log::{error, info, warn};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::SystemTime,
+};
+use tokio::{
+    io::AsyncRead,
+    sync::{mpsc, oneshot},
+};
+
+/// Number of requests buffered while sent to the collector
+const REQUESTS_BUFFER: usize = 16;
+
+pub(crate) type FrameReader = Box<dyn AsyncRead + Send + Unpin>;
+
+type PendingRequests = HashMap<u64, oneshot::Sender<Result<Value, ErrorReport>>>;
+
+/// Connection of a window to the collector
+pub(crate) struct CollectorClient {
+    frames: mpsc::Sender<Frame>,
+    pending: Mutex<PendingRequests>,
+    next_id: AtomicU64,
+    detached: AtomicBool,
+}
+
+impl CollectorClient {
+    /// Connects to the collector if one is running, returns the client
+    /// and the frames sent by the collector
+    #[cfg(unix)]
+    pub async fn connect() -> Option<(Self, FrameReader)> {
+        let stream = tokio::net::UnixStream::connect(super::socket_path())
+            .await
+            .ok()?;
+        let (reader, mut writer) = stream.into_split();
+
+        let (frames, mut frames_receiver) = mpsc::channel::<Frame>(REQUESTS_BUFFER);
+        tokio::spawn(async move {
+            while let Some(frame) = frames_receiver.recv().await {
+                if let Err(error) = write_frame(&mut writer, &frame).await {
+                    error!("Cannot send a request to the collector due to {error:?}");
+                    break;
+                }
+            }
+        });
+
+        let client = Self {
+            frames,
+            pending: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+            detached: AtomicBool::new(false),
+        };
+        Some((client, Box::new(reader)))
+    }
+
+    #[cfg(not(unix))]
+    pub async fn connect() -> Option<(Self, FrameReader)> {
+        None
+    }
+
+    /// Runs a request in the collector and returns its result
+    ///
+    /// # Error
+    ///
+    /// If the request failed or the collector stopped, an error will be returned
+    pub async fn request<T: DeserializeOwned>(&self, request: Request) -> Result<T, TraceError> {
+        let detached = || TraceError::Collector("the collector stopped".to_owned());
+        if self.detached.load(Ordering::Relaxed) {
+            return Err(detached());
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, sender);
+
+        self.frames
+            .send(Frame::Message(Message::Request { id, request }))
+            .await
+            .map_err(|_| detached())?;
+        let value = receiver
+            .await
+            .map_err(|_| detached())?
+            .map_err(TraceError::Forwarded)?;
+
+        Ok(serde_json::from_value(value)?)
+    }
+
+    fn resolve(&self, id: u64, result: Result<Value, ErrorReport>) {
+        if let Some(sender) = self.pending.lock().unwrap().remove(&id) {
+            sender.send(result).ok();
+        }
+    }
+
+    /// Fails the requests waiting for a response
+    fn detach(&self) {
+        self.detached.store(true, Ordering::Relaxed);
+        self.pending.lock().unwrap().clear();
+    }
+}
+
+/// Applies the data sent by the collector to the state, until the collector stops
+pub(crate) async fn receive(state_manager: Arc<StateManager>, mut reader: FrameReader) {
+    info!("Attached to the collector");
+
+    loop {
+        let frame = match read_frame(&mut reader).await {
+            Ok(Some(frame)) => frame,
+            Ok(None) => {
+                warn!("The collector stopped, the data will not be updated anymore");
+                break;
+            }
+            Err(error) => {
+                error!("Cannot read the data sent by the collector due to {error:?}");
+                break;
+            }
+        };
+
+        match frame {
+            Frame::Update(app_id, update) => {
+                state_manager
+                    .handle_update(app_id, Arc::unwrap_or_clone(update), SystemTime::now())
+                    .await;
+            }
+            Frame::TaskDetails(app_id, details) => {
+                state_manager
+                    .state
+                    .handle_task_details(app_id, Arc::unwrap_or_clone(details))
+                    .await;
+            }
+            Frame::Message(Message::Snapshot {
+                applications,
+                tasks,
+                settings,
+            }) => {
+                // The analyzers of the window run with the collector's settings
+                state_manager.state.store_settings(settings).await;
+                state_manager.state.replace_apps(applications).await;
+                state_manager.state.replace_tasks(tasks).await;
+            }
+            Frame::Message(Message::Applications(applications)) => {
+                state_manager.state.replace_apps(applications).await;
+            }
+            Frame::Message(Message::Response { id, result }) => {
+                if let Some(collector) = &state_manager.collector {
+                    collector.resolve(id, result);
+                }
+            }
+            // Only windows send requests
+            Frame::Message(Message::Request { .. }) => {}
+        }
+    }
+
+    if let Some(collector) = &state_manager.collector {
+        collector.detach();
+    }
+}
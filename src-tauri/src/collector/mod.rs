@@ -0,0 +1,183 @@
+//! Collector daemon, keeping the connections and the storage of the
+//! applications while no window is open
+//!
+//! The collector is started with `tokio-display --collector`. A window opened
+//! while it runs attaches to it over a local socket: it receives the stored
+//! applications and tasks, then the updates of the applications, and sends
+//! the operations on the applications to the collector.
+//!
+//! Every frame is a tag, the length of the payload and the payload. Updates
+//! and task details are protobuf encoded, the other messages are JSON.
+
+pub(crate) mod client;
+pub(crate) mod server;
+
+use crate::domain::{
+    application::{Application, PreConnectHook},
+    secrets::Metadata,
+    settings::Settings,
+    Task,
+};
+use crate::error::ErrorReport;
+use crate::state_manager::state::State;
+use console_api::{instrument::Update, tasks::TaskDetails};
+use prost::Message as _;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{Error as IoError, ErrorKind},
+    path::PathBuf,
+    sync::Arc,
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use url::Url;
+use uuid::Uuid;
+
+const SOCKET_FILE: &str = "collector.sock";
+
+/// Frames larger than this are considered corrupted
+const MAX_FRAME_SIZE: u32 = 64 * 1024 * 1024;
+
+const MESSAGE_TAG: u8 = 0;
+const UPDATE_TAG: u8 = 1;
+const TASK_DETAILS_TAG: u8 = 2;
+
+pub(crate) fn socket_path() -> PathBuf {
+    State::storage_folder().join(SOCKET_FILE)
+}
+
+/// Operations the attached windows ask the collector to run
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "request")]
+pub(crate) enum Request {
+    AddApplication {
+        title: String,
+        url: Url,
+        #[serde(default)]
+        pre_connect: Option<PreConnectHook>,
+        #[serde(default)]
+        metadata: Metadata,
+    },
+    DeleteApplication {
+        app_id: Uuid,
+    },
+    DisableApplication {
+        app_id: Uuid,
+    },
+    SetApplicationProxy {
+        app_id: Uuid,
+        port: Option<u16>,
+    },
+    WatchTaskDetails {
+        app_id: Uuid,
+        task_ids: Vec<u64>,
+    },
+    PurgeHistory {
+        app_id: Uuid,
+    },
+    UpdateApplicationMetadata {
+        app_id: Uuid,
+        #[serde(default)]
+        metadata: Metadata,
+    },
+    GetSettings,
+    UpdateSettings {
+        settings: Settings,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) enum Message {
+    Request {
+        id: u64,
+        request: Request,
+    },
+    Response {
+        id: u64,
+        result: Result<serde_json::Value, ErrorReport>,
+    },
+    /// Data stored by the collector, sent once attached
+    Snapshot {
+        applications: Vec<Application>,
+        tasks: Vec<Task>,
+        #[serde(default)]
+        settings: Settings,
+    },
+    /// Sent periodically, as the applications change
+    Applications(Vec<Application>),
+}
+
+pub(crate) enum Frame {
+    Message(Message),
+    Update(Uuid, Arc<Update>),
+    TaskDetails(Uuid, Arc<TaskDetails>),
+}
+
+pub(crate) async fn write_frame(
+    writer: &mut (impl AsyncWrite + Unpin),
+    frame: &Frame,
+) -> Result<(), IoError> {
+    let (tag, payload) = match frame {
+        Frame::Message(message) => (MESSAGE_TAG, serde_json::to_vec(message)?),
+        Frame::Update(app_id, update) => (
+            UPDATE_TAG,
+            [app_id.as_bytes().as_slice(), &update.encode_to_vec()].concat(),
+        ),
+        Frame::TaskDetails(app_id, details) => (
+            TASK_DETAILS_TAG,
+            [app_id.as_bytes().as_slice(), &details.encode_to_vec()].concat(),
+        ),
+    };
+
+    writer.write_u8(tag).await?;
+    writer.write_u32(payload.len() as u32).await?;
+    writer.write_all(&payload).await?;
+    writer.flush().await
+}
+
+/// Reads the next frame, returns nothing once the other side closed the socket
+pub(crate) async fn read_frame(
+    reader: &mut (impl AsyncRead + Unpin),
+) -> Result<Option<Frame>, IoError> {
+    let tag = match reader.read_u8().await {
+        Ok(tag) => tag,
+        Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    let length = reader.read_u32().await?;
+    if length > MAX_FRAME_SIZE {
+        return Err(invalid(format!("frame of {length} bytes")));
+    }
+    let mut payload = vec![0; length as usize];
+    reader.read_exact(&mut payload).await?;
+
+    let frame = match tag {
+        MESSAGE_TAG => Frame::Message(serde_json::from_slice(&payload)?),
+        UPDATE_TAG => {
+            let (app_id, message) = split_app_id(&payload)?;
+            Frame::Update(app_id, Arc::new(Update::decode(message).map_err(invalid)?))
+        }
+        TASK_DETAILS_TAG => {
+            let (app_id, message) = split_app_id(&payload)?;
+            Frame::TaskDetails(
+                app_id,
+                Arc::new(TaskDetails::decode(message).map_err(invalid)?),
+            )
+        }
+        tag => return Err(invalid(format!("unknown frame tag {tag}"))),
+    };
+
+    Ok(Some(frame))
+}
+
+fn invalid(error: impl ToString) -> IoError {
+    IoError::new(ErrorKind::InvalidData, error.to_string())
+}
+
+/// Splits the application id prefixing a protobuf message
+fn split_app_id(payload: &[u8]) -> Result<(Uuid, &[u8]), IoError> {
+    if payload.len() < 16 {
+        return Err(invalid("missing application id"));
+    }
+    let (app_id, message) = payload.split_at(16);
+    Ok((Uuid::from_slice(app_id).map_err(invalid)?, message))
+}
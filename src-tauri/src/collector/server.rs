@@ -0,0 +1,213 @@
+use super::{read_frame, write_frame, Frame, Message, Request};
+use crate::error::Error as TraceError;
+use crate::state_manager::StateManager;
+use log::{info, warn};
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::{broadcast::error::RecvError, mpsc},
+    task::JoinHandle,
+    time::{interval, Duration},
+};
+
+/// Number of frames buffered for every window
+const FRAMES_BUFFER: usize = 64;
+
+/// Period of the applications list sent to the windows
+const APPLICATIONS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Accepts the windows attaching to the collector, until the socket fails
+///
+/// # Error
+///
+/// If the socket cannot be listened on (eg: another collector is running),
+/// an error will be returned
+#[cfg(unix)]
+pub(crate) async fn serve(state_manager: Arc<StateManager>) -> Result<(), TraceError> {
+    use super::socket_path;
+    use tokio::net::{UnixListener, UnixStream};
+
+    let path = socket_path();
+    if path.exists() {
+        if UnixStream::connect(&path).await.is_ok() {
+            return Err(TraceError::Collector(format!(
+                "another collector is listening on {path:?}"
+            )));
+        }
+        // Left by a collector which did not stop properly
+        tokio::fs::remove_file(&path).await.ok();
+    }
+
+    let listener = UnixListener::bind(&path).map_err(|error| TraceError::CannotListen {
+        error: error.into(),
+        address: path.to_string_lossy().to_string(),
+    })?;
+    info!("Collector listening on {path:?}");
+
+    loop {
+        let (stream, _) = listener
+            .accept()
+            .await
+            .map_err(|error| TraceError::Collector(error.to_string()))?;
+        info!("A window attached to the collector");
+        let (reader, writer) = stream.into_split();
+        tokio::spawn(serve_window(state_manager.clone(), reader, writer));
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) async fn serve(_state_manager: Arc<StateManager>) -> Result<(), TraceError> {
+    Err(TraceError::Collector(
+        "the collector is only supported on unix systems".to_owned(),
+    ))
+}
+
+/// Sends the data of the collector to a window and runs its requests,
+/// until the window detaches
+async fn serve_window(
+    state_manager: Arc<StateManager>,
+    mut reader: impl AsyncRead + Unpin + Send + 'static,
+    mut writer: impl AsyncWrite + Unpin + Send + 'static,
+) {
+    let (frames, mut frames_receiver) = mpsc::channel::<Frame>(FRAMES_BUFFER);
+    tokio::spawn(async move {
+        while let Some(frame) = frames_receiver.recv().await {
+            if write_frame(&mut writer, &frame).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let request_frames = frames.clone();
+    let request_state_manager = state_manager.clone();
+    let mut requests: JoinHandle<()> = tokio::spawn(async move {
+        while let Ok(Some(frame)) = read_frame(&mut reader).await {
+            // Windows only send requests
+            let Frame::Message(Message::Request { id, request }) = frame else {
+                continue;
+            };
+            let result = run_request(&request_state_manager, request)
+                .await
+                .map_err(|error| error.report());
+            let response = Frame::Message(Message::Response { id, result });
+            if request_frames.send(response).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    // A window lagging behind receives the whole state again
+    'attach: loop {
+        let (snapshots, mut updates, mut task_details) =
+            state_manager.proxy_hub.subscribe_all().await;
+
+        let applications = state_manager.current_applications().await;
+        let tasks = state_manager.state.get_tasks_snapshot().await;
+        let settings = state_manager.state.get_settings().await;
+        let snapshot = Message::Snapshot {
+            applications: applications
+                .iter()
+                .map(|app| app.as_ref().clone())
+                .collect(),
+            tasks: tasks.values().map(|task| task.as_ref().clone()).collect(),
+            settings,
+        };
+        if frames.send(Frame::Message(snapshot)).await.is_err() {
+            break;
+        }
+        for (app_id, update) in snapshots {
+            if frames
+                .send(Frame::Update(app_id, Arc::new(update)))
+                .await
+                .is_err()
+            {
+                break 'attach;
+            }
+        }
+
+        let mut applications_interval = interval(APPLICATIONS_INTERVAL);
+        loop {
+            let frame = tokio::select! {
+                update = updates.recv() => match update {
+                    Ok((app_id, update)) => Frame::Update(app_id, update),
+                    Err(RecvError::Lagged(_)) => {
+                        warn!("A window fell behind the updates, sending the whole state again");
+                        continue 'attach;
+                    }
+                    Err(RecvError::Closed) => break 'attach,
+                },
+                details = task_details.recv() => match details {
+                    Ok((app_id, details)) => Frame::TaskDetails(app_id, details),
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break 'attach,
+                },
+                _ = applications_interval.tick() => {
+                    let applications = state_manager.current_applications().await;
+                    Frame::Message(Message::Applications(
+                        applications.iter().map(|app| app.as_ref().clone()).collect(),
+                    ))
+                }
+                // The window closed the socket
+                _ = &mut requests => break 'attach,
+            };
+            if frames.send(frame).await.is_err() {
+                break 'attach;
+            }
+        }
+    }
+
+    requests.abort();
+    info!("A window detached from the collector");
+}
+
+async fn run_request(
+    state_manager: &Arc<StateManager>,
+    request: Request,
+) -> Result<Value, TraceError> {
+    info!("Received request {request:?} from a window");
+    match request {
+        Request::AddApplication {
+            title,
+            url,
+            pre_connect,
+            metadata,
+        } => {
+            let app_id = state_manager
+                .add_application(title, url, pre_connect, metadata.0)
+                .await?;
+            Ok(serde_json::to_value(app_id)?)
+        }
+        Request::DeleteApplication { app_id } => {
+            state_manager.delete_connection(app_id).await;
+            Ok(Value::Null)
+        }
+        Request::DisableApplication { app_id } => {
+            state_manager.disable_application(app_id).await?;
+            Ok(Value::Null)
+        }
+        Request::SetApplicationProxy { app_id, port } => {
+            state_manager.set_application_proxy(app_id, port).await?;
+            Ok(Value::Null)
+        }
+        Request::PurgeHistory { app_id } => {
+            state_manager.purge_application_history(app_id).await?;
+            Ok(Value::Null)
+        }
+        Request::UpdateApplicationMetadata { app_id, metadata } => {
+            state_manager
+                .update_application_metadata(app_id, metadata.0)
+                .await?;
+            Ok(Value::Null)
+        }
+        Request::WatchTaskDetails { app_id, task_ids } => {
+            state_manager.watch_task_details(app_id, &task_ids).await;
+            Ok(Value::Null)
+        }
+        Request::GetSettings => Ok(serde_json::to_value(state_manager.settings().await?)?),
+        Request::UpdateSettings { settings } => {
+            state_manager.update_settings(settings).await?;
+            Ok(Value::Null)
+        }
+    }
+}
@@ -1,21 +1,43 @@
 use log::info;
-use std::sync::Arc;
-use tauri::State;
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use tauri::{AppHandle, State};
+use tauri_plugin_dialog::DialogExt;
+use tokio::sync::oneshot;
 use uuid::Uuid;
 
-use crate::error::Error;
-use crate::state_manager::StateManager;
+use crate::discovery::DiscoveredEndpoint;
+use crate::domain::application::{OutputLine, PreConnectHook};
+use crate::domain::data_loss::DataLoss;
+use crate::error::{Error, ErrorReport};
+use crate::state_manager::{IngestionStats, StateManager};
 
 #[tauri::command]
 pub async fn applications_add(
     state_manager: State<'_, Arc<StateManager>>,
     title: String,
     url: &str,
+    pre_connect: Option<PreConnectHook>,
+    metadata: Option<HashMap<String, String>>,
 ) -> Result<Uuid, Error> {
     info!("Received command to add application with title {title} and url {url}");
 
     let url = url.try_into()?;
-    state_manager.add_application(title, url).await
+    state_manager
+        .add_application(title, url, pre_connect, metadata.unwrap_or_default())
+        .await
+}
+
+/// Replaces the gRPC metadata sent to an application, eg: an expired token
+#[tauri::command]
+pub async fn update_application_metadata(
+    state_manager: State<'_, Arc<StateManager>>,
+    uuid: Uuid,
+    metadata: HashMap<String, String>,
+) -> Result<(), Error> {
+    info!("Received command to update the metadata of application {uuid}");
+    state_manager
+        .update_application_metadata(uuid, metadata)
+        .await
 }
 
 #[tauri::command]
@@ -37,3 +59,110 @@ pub async fn disable_app(
 ) -> Result<(), Error> {
     state_manager.disable_application(uuid).await
 }
+
+/// Searches the console servers running on the machine, they can then
+/// be registered with `applications_add`
+#[tauri::command]
+pub async fn discover_applications(
+    state_manager: State<'_, Arc<StateManager>>,
+) -> Result<Vec<DiscoveredEndpoint>, Error> {
+    info!("Received command to discover the applications");
+    Ok(state_manager.discover_applications().await)
+}
+
+/// Runs a program with console instrumentation enabled on a free port,
+/// and connects to it
+#[tauri::command]
+pub async fn launch_application(
+    app_handle: AppHandle,
+    state_manager: State<'_, Arc<StateManager>>,
+    title: Option<String>,
+    program: String,
+    args: Vec<String>,
+    current_dir: Option<PathBuf>,
+) -> Result<Uuid, Error> {
+    info!("Received command to launch {program} with arguments {args:?}");
+    state_manager
+        .launch_application(&app_handle, title, program, args, current_dir)
+        .await
+}
+
+/// Returns the latest lines written by a launched application
+#[tauri::command]
+pub async fn application_output(
+    state_manager: State<'_, Arc<StateManager>>,
+    uuid: Uuid,
+) -> Result<Vec<OutputLine>, Error> {
+    Ok(state_manager.application_output(uuid).await)
+}
+
+/// Returns how the events of the connected applications are ingested, by application
+#[tauri::command]
+pub async fn ingestion_stats(
+    state_manager: State<'_, Arc<StateManager>>,
+) -> Result<HashMap<Uuid, IngestionStats>, Error> {
+    Ok(state_manager.ingestion_stats())
+}
+
+/// Removes the completed tasks and the metrics history of an application
+#[tauri::command]
+pub async fn purge_application_history(
+    state_manager: State<'_, Arc<StateManager>>,
+    uuid: Uuid,
+) -> Result<(), Error> {
+    info!("Received command to purge the history of application {uuid}");
+    state_manager.purge_application_history(uuid).await
+}
+
+/// Returns the events the applications could not send, by application
+#[tauri::command]
+pub async fn data_loss(
+    state_manager: State<'_, Arc<StateManager>>,
+) -> Result<HashMap<Uuid, DataLoss>, Error> {
+    Ok(state_manager.data_loss().await)
+}
+
+/// Returns why the applications could not be connected to, by application
+#[tauri::command]
+pub async fn connection_errors(
+    state_manager: State<'_, Arc<StateManager>>,
+) -> Result<HashMap<Uuid, ErrorReport>, Error> {
+    Ok(state_manager.connection_errors().await)
+}
+
+/// Re-exposes an application to other consoles (eg: tokio-console)
+/// on the given port, or stops if no port is given
+#[tauri::command]
+pub async fn set_application_proxy(
+    state_manager: State<'_, Arc<StateManager>>,
+    uuid: Uuid,
+    port: Option<u16>,
+) -> Result<(), Error> {
+    info!("Received command to set the proxy port of application {uuid} to {port:?}");
+    state_manager.set_application_proxy(uuid, port).await
+}
+
+/// Asks the user for a console-subscriber recording and registers it
+/// as a read-only application
+///
+/// Returns the id of the application, or nothing if the user cancelled
+#[tauri::command]
+pub async fn import_recording(
+    app_handle: AppHandle,
+    state_manager: State<'_, Arc<StateManager>>,
+) -> Result<Option<Uuid>, Error> {
+    let (path_sender, path_receiver) = oneshot::channel();
+    app_handle.dialog().file().pick_file(move |path| {
+        path_sender.send(path).ok();
+    });
+
+    let Some(path) = path_receiver.await.ok().flatten() else {
+        return Ok(None);
+    };
+    let path = path
+        .into_path()
+        .map_err(|err| Error::InvalidPath(err.to_string()))?;
+
+    info!("Received command to import the recording {path:?}");
+    state_manager.import_recording(&path).await.map(Some)
+}
@@ -0,0 +1,42 @@
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+use tauri_plugin_dialog::DialogExt;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use crate::error::Error;
+use crate::export::ExportFormat;
+use crate::state_manager::StateManager;
+
+/// Asks the user where to save the export of an application and writes it
+///
+/// Returns the path of the written file, or nothing if the user cancelled
+#[tauri::command]
+pub async fn export_application(
+    app_handle: AppHandle,
+    state_manager: State<'_, Arc<StateManager>>,
+    app_id: Uuid,
+    format: ExportFormat,
+) -> Result<Option<String>, Error> {
+    let (path_sender, path_receiver) = oneshot::channel();
+    app_handle
+        .dialog()
+        .file()
+        .set_file_name(format!("{app_id}.{}", format.extension()))
+        .add_filter(format.description(), &[format.extension()])
+        .save_file(move |path| {
+            path_sender.send(path).ok();
+        });
+
+    let Some(path) = path_receiver.await.ok().flatten() else {
+        return Ok(None);
+    };
+    let path = path
+        .into_path()
+        .map_err(|err| Error::InvalidPath(err.to_string()))?;
+
+    state_manager
+        .export_application(app_id, format, &path)
+        .await?;
+    Ok(Some(path.to_string_lossy().to_string()))
+}
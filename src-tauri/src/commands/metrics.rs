@@ -0,0 +1,67 @@
+use std::{sync::Arc, time::Duration};
+use tauri::State;
+use uuid::Uuid;
+
+use crate::analyzers::scheduling::SchedulingReport;
+use crate::error::Error;
+use crate::state_manager::history::{ActivityOrder, Metrics, MetricsSample, TaskActivity};
+use crate::state_manager::StateManager;
+
+/// Returns the metrics history of an application, or of one of its tasks if
+/// `task_id` is set, between `from` and `to` (milliseconds since UNIX epoch)
+#[tauri::command]
+pub async fn metrics_history(
+    state_manager: State<'_, Arc<StateManager>>,
+    app_id: Uuid,
+    task_id: Option<u64>,
+    from: Option<u64>,
+    to: Option<u64>,
+) -> Result<Vec<MetricsSample>, Error> {
+    Ok(state_manager
+        .metrics_history(app_id, task_id, from, to)
+        .await)
+}
+
+/// Returns the scheduling latencies of an application and of its live tasks,
+/// with whether its runtime keeps up with the woken tasks
+#[tauri::command]
+pub async fn scheduling_report(
+    state_manager: State<'_, Arc<StateManager>>,
+    app_id: Uuid,
+) -> Result<SchedulingReport, Error> {
+    state_manager.scheduling_report(app_id).await
+}
+
+/// Returns the mean rates of an application, or of one of its tasks if
+/// `task_id` is set, over the latest `window` seconds
+#[tauri::command]
+pub async fn windowed_rates(
+    state_manager: State<'_, Arc<StateManager>>,
+    app_id: Uuid,
+    task_id: Option<u64>,
+    window: Option<u64>,
+) -> Result<Option<Metrics>, Error> {
+    Ok(state_manager
+        .windowed_rates(app_id, task_id, window.map(Duration::from_secs))
+        .await)
+}
+
+/// Returns the live tasks the most active over the latest `window` seconds,
+/// of every application if `app_id` is not set
+#[tauri::command]
+pub async fn top_tasks(
+    state_manager: State<'_, Arc<StateManager>>,
+    app_id: Option<Uuid>,
+    window: Option<u64>,
+    order: Option<ActivityOrder>,
+    limit: Option<usize>,
+) -> Result<Vec<TaskActivity>, Error> {
+    Ok(state_manager
+        .top_tasks(
+            app_id,
+            window.map(Duration::from_secs),
+            order.unwrap_or_default(),
+            limit,
+        )
+        .await)
+}
@@ -1 +1,5 @@
 pub mod applications;
+pub mod export;
+pub mod metrics;
+pub mod settings;
+pub mod tasks;
@@ -0,0 +1,22 @@
+use log::info;
+use std::sync::Arc;
+use tauri::State;
+
+use crate::domain::settings::Settings;
+use crate::error::Error;
+use crate::state_manager::StateManager;
+
+#[tauri::command]
+pub async fn get_settings(state_manager: State<'_, Arc<StateManager>>) -> Result<Settings, Error> {
+    state_manager.settings().await
+}
+
+/// Stores the settings and applies them (eg: starts or stops the metrics endpoint)
+#[tauri::command]
+pub async fn update_settings(
+    state_manager: State<'_, Arc<StateManager>>,
+    settings: Settings,
+) -> Result<(), Error> {
+    info!("Received command to update the settings");
+    state_manager.update_settings(settings).await
+}
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+use tauri::State;
+use uuid::Uuid;
+
+use crate::analyzers::blocking::BlockingReport;
+use crate::analyzers::grouping::{TaskGroup, TaskGrouping};
+use crate::analyzers::leak::LeakReport;
+use crate::domain::TaskHistograms;
+use crate::error::Error;
+use crate::query::{TaskPage, TaskQuery};
+use crate::state_manager::StateManager;
+
+/// Returns the poll and scheduled times histograms of a task
+#[tauri::command]
+pub async fn task_histograms(
+    state_manager: State<'_, Arc<StateManager>>,
+    app_id: Uuid,
+    task_id: u64,
+) -> Result<Option<TaskHistograms>, Error> {
+    Ok(state_manager
+        .task_histograms(app_id, task_id)
+        .await
+        .map(|histograms| histograms.as_ref().clone()))
+}
+
+/// Returns the histograms of all the tasks spawned at the same location, merged
+#[tauri::command]
+pub async fn location_histograms(
+    state_manager: State<'_, Arc<StateManager>>,
+    app_id: Uuid,
+    location: &str,
+) -> Result<TaskHistograms, Error> {
+    Ok(state_manager.location_histograms(app_id, location).await)
+}
+
+/// Returns the tasks matching the query's filter, sorted and paginated
+#[tauri::command]
+pub async fn tasks_query(
+    state_manager: State<'_, Arc<StateManager>>,
+    query: TaskQuery,
+) -> Result<TaskPage, Error> {
+    state_manager.query_tasks(&query).await
+}
+
+/// Aggregates the tasks by spawn location, name, kind or any query field
+#[tauri::command]
+pub async fn task_groups(
+    state_manager: State<'_, Arc<StateManager>>,
+    grouping: TaskGrouping,
+) -> Result<Vec<TaskGroup>, Error> {
+    state_manager.task_groups(&grouping).await
+}
+
+/// Sets the grouping periodically sent with the `update:task_groups` event,
+/// none to stop sending it
+#[tauri::command]
+pub async fn watch_task_groups(
+    state_manager: State<'_, Arc<StateManager>>,
+    grouping: Option<TaskGrouping>,
+) -> Result<(), Error> {
+    state_manager.watch_task_groups(grouping).await
+}
+
+/// Returns the suspected task leaks, of every application if none is given
+#[tauri::command]
+pub async fn task_leaks(
+    state_manager: State<'_, Arc<StateManager>>,
+    app_id: Option<Uuid>,
+) -> Result<Vec<LeakReport>, Error> {
+    Ok(state_manager.task_leaks(app_id))
+}
+
+/// Returns the polls of an application which exceeded the budget, and the
+/// tasks blocking their worker thread the longest
+#[tauri::command]
+pub async fn blocking_report(
+    state_manager: State<'_, Arc<StateManager>>,
+    app_id: Uuid,
+) -> Result<BlockingReport, Error> {
+    Ok(state_manager.blocking_report(app_id))
+}
@@ -0,0 +1,146 @@
+//! Search of the console-subscriber servers running on the machine
+//!
+//! Candidates are the ports of the configured ranges, and the ports the
+//! processes are seen listening on (Linux only). A candidate is a console
+//! server if it answers the `Instrument` service.
+
+mod procfs;
+
+use crate::domain::{application::Application, settings::DiscoverySettings};
+use console_api::instrument::{instrument_client::InstrumentClient, InstrumentRequest};
+use log::{debug, info};
+use procfs::ListeningSocket;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{sync::Semaphore, task::JoinSet, time::timeout};
+use tonic::transport::Endpoint;
+
+/// Time given to a candidate to answer
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Candidates probed at the same time
+const MAX_CONCURRENT_PROBES: usize = 32;
+
+/// Console server found on the machine
+#[derive(Serialize, Clone, Debug)]
+pub struct DiscoveredEndpoint {
+    pub url: String,
+    pub port: u16,
+    /// Process listening on the port, if it could be found
+    pub pid: Option<u32>,
+    pub command_line: Option<String>,
+    /// Whether an application is already registered with this address
+    pub registered: bool,
+}
+
+/// Address to connect to, to reach a socket listening on the given address
+fn connect_address(address: SocketAddr) -> SocketAddr {
+    match address.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => (Ipv4Addr::LOCALHOST, address.port()).into(),
+        IpAddr::V6(ip) if ip.is_unspecified() => (Ipv6Addr::LOCALHOST, address.port()).into(),
+        _ => address,
+    }
+}
+
+/// Returns whether the console server answers on the address
+async fn probe(address: SocketAddr) -> bool {
+    let Ok(endpoint) = Endpoint::from_shared(format!("http://{address}")) else {
+        return false;
+    };
+    let endpoint = endpoint
+        .connect_timeout(PROBE_TIMEOUT)
+        .timeout(PROBE_TIMEOUT);
+
+    let handshake = async {
+        let channel = endpoint.connect().await.ok()?;
+        InstrumentClient::new(channel)
+            .watch_updates(InstrumentRequest {})
+            .await
+            .ok()
+    };
+    matches!(timeout(PROBE_TIMEOUT, handshake).await, Ok(Some(_)))
+}
+
+fn is_registered(address: SocketAddr, applications: &[Arc<Application>]) -> bool {
+    applications.iter().any(|application| {
+        let url = application.url();
+        let same_host = match url.host_str() {
+            Some("localhost") => address.ip().is_loopback(),
+            Some(host) => host
+                .trim_matches(['[', ']'])
+                .parse::<IpAddr>()
+                .is_ok_and(|ip| ip == address.ip()),
+            None => false,
+        };
+        same_host && url.port_or_known_default() == Some(address.port())
+    })
+}
+
+/// Probes the candidates and returns the console servers found
+pub(crate) async fn discover(
+    settings: &DiscoverySettings,
+    applications: &[Arc<Application>],
+) -> Vec<DiscoveredEndpoint> {
+    let sockets = tokio::task::spawn_blocking(procfs::listening_sockets)
+        .await
+        .unwrap_or_default();
+
+    // Candidates with the process listening on them, if known
+    let own_pid = std::process::id();
+    let mut candidates: BTreeMap<SocketAddr, Option<&ListeningSocket>> = BTreeMap::new();
+    for socket in &sockets {
+        // The proxies of this application are console servers too
+        if socket.pid != Some(own_pid) {
+            candidates.insert(connect_address(socket.address), Some(socket));
+        }
+    }
+    let pids_by_port: HashMap<u16, &ListeningSocket> = sockets
+        .iter()
+        .map(|socket| (socket.address.port(), socket))
+        .collect();
+    for range in &settings.port_ranges {
+        for port in range.start..=range.end {
+            let socket = pids_by_port.get(&port).copied();
+            if socket.is_none_or(|socket| socket.pid != Some(own_pid)) {
+                candidates
+                    .entry((Ipv4Addr::LOCALHOST, port).into())
+                    .or_insert(socket);
+            }
+        }
+    }
+    debug!("Probing {} candidate console servers", candidates.len());
+
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_PROBES));
+    let mut probes = JoinSet::new();
+    for (address, socket) in candidates {
+        let pid = socket.and_then(|socket| socket.pid);
+        let permits = permits.clone();
+        probes.spawn(async move {
+            let _permit = permits.acquire_owned().await.ok()?;
+            probe(address).await.then_some((address, pid))
+        });
+    }
+
+    let mut endpoints = Vec::new();
+    while let Some(result) = probes.join_next().await {
+        let Ok(Some((address, pid))) = result else {
+            continue;
+        };
+        endpoints.push(DiscoveredEndpoint {
+            url: format!("http://{address}"),
+            port: address.port(),
+            pid,
+            command_line: pid.and_then(procfs::command_line),
+            registered: is_registered(address, applications),
+        });
+    }
+    endpoints.sort_by_key(|endpoint| endpoint.port);
+    info!("Discovered {} console servers", endpoints.len());
+
+    endpoints
+}
@@ -0,0 +1,133 @@
+//! Listening sockets and their processes, read from `/proc`
+
+use std::net::SocketAddr;
+
+/// TCP socket in the listening state
+pub(crate) struct ListeningSocket {
+    pub address: SocketAddr,
+    /// Owner of the socket, unknown if it belongs to another user
+    pub pid: Option<u32>,
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn listening_sockets() -> Vec<ListeningSocket> {
+    linux::listening_sockets()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn listening_sockets() -> Vec<ListeningSocket> {
+    Vec::new()
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn command_line(pid: u32) -> Option<String> {
+    let command_line = std::fs::read(format!("/proc/{pid}/cmdline")).ok()?;
+    let arguments: Vec<_> = command_line
+        .split(|byte| *byte == 0)
+        .filter(|argument| !argument.is_empty())
+        .map(String::from_utf8_lossy)
+        .collect();
+    (!arguments.is_empty()).then(|| arguments.join(" "))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn command_line(_pid: u32) -> Option<String> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::ListeningSocket;
+    use std::{
+        collections::HashMap,
+        fs,
+        net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    };
+
+    /// State of the listening sockets in `/proc/net/tcp`
+    const LISTEN_STATE: &str = "0A";
+
+    pub(super) fn listening_sockets() -> Vec<ListeningSocket> {
+        let owners = socket_owners();
+
+        ["/proc/net/tcp", "/proc/net/tcp6"]
+            .iter()
+            .filter_map(|path| fs::read_to_string(path).ok())
+            .flat_map(|table| {
+                table
+                    .lines()
+                    .skip(1)
+                    .filter_map(parse_socket)
+                    .collect::<Vec<_>>()
+            })
+            .map(|(address, inode)| ListeningSocket {
+                address,
+                pid: owners.get(&inode).copied(),
+            })
+            .collect()
+    }
+
+    /// Parses a line of a socket table, eg:
+    /// `0: 0100007F:1A0D 00000000:0000 0A ... 0 12345 ...`,
+    /// returns the local address and the inode of listening sockets
+    fn parse_socket(line: &str) -> Option<(SocketAddr, u64)> {
+        let columns: Vec<&str> = line.split_whitespace().collect();
+        if columns.get(3) != Some(&LISTEN_STATE) {
+            return None;
+        }
+
+        let (ip, port) = columns.get(1)?.split_once(':')?;
+        let port = u16::from_str_radix(port, 16).ok()?;
+        // Addresses are written as 32 bits words, in the byte order of the host
+        let mut bytes = Vec::with_capacity(16);
+        for word in ip.as_bytes().chunks(8) {
+            let word = u32::from_str_radix(std::str::from_utf8(word).ok()?, 16).ok()?;
+            bytes.extend_from_slice(&word.to_ne_bytes());
+        }
+        let ip = match bytes.len() {
+            4 => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(bytes).ok()?)),
+            16 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?)),
+            _ => return None,
+        };
+        let inode = columns.get(9)?.parse().ok()?;
+
+        Some((SocketAddr::new(ip, port), inode))
+    }
+
+    /// Returns the process owning every socket inode, only the
+    /// processes of the current user can be inspected
+    fn socket_owners() -> HashMap<u64, u32> {
+        let mut owners = HashMap::new();
+        let Ok(processes) = fs::read_dir("/proc") else {
+            return owners;
+        };
+
+        for process in processes.flatten() {
+            let Some(pid) = process
+                .file_name()
+                .to_str()
+                .and_then(|name| name.parse().ok())
+            else {
+                continue;
+            };
+            let Ok(descriptors) = fs::read_dir(process.path().join("fd")) else {
+                continue;
+            };
+            for descriptor in descriptors.flatten() {
+                let Ok(target) = fs::read_link(descriptor.path()) else {
+                    continue;
+                };
+                let inode = target
+                    .to_str()
+                    .and_then(|target| target.strip_prefix("socket:["))
+                    .and_then(|target| target.strip_suffix(']'))
+                    .and_then(|inode| inode.parse().ok());
+                if let Some(inode) = inode {
+                    owners.insert(inode, pid);
+                }
+            }
+        }
+
+        owners
+    }
+}
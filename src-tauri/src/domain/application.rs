@@ -6,6 +6,7 @@ use crate::mappers::read_file;
 use crate::state_manager::connection_manager::{Command, Connection};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
 use tauri::Url;
 use uuid::Uuid;
 
@@ -14,6 +15,53 @@ pub(crate) enum ApplicationState {
     #[default]
     Disabled,
     Enabled,
+    /// Imported from a recording, never connected
+    Offline,
+    /// Launched by the application, its process ended
+    Exited,
+}
+
+/// Output stream of a launched application
+#[derive(Debug, Serialize, Deserialize, PartialEq, Copy, Clone)]
+pub(crate) enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Line written by a launched application
+#[derive(Debug, Serialize, Clone)]
+pub(crate) struct OutputLine {
+    pub stream: OutputStream,
+    pub line: String,
+    pub at: SystemTime,
+}
+
+/// Status of the connection to an application, as reported
+/// by the connection manager
+#[derive(Default, Debug, Serialize, PartialEq, Copy, Clone)]
+pub(crate) enum ConnectionStatus {
+    #[default]
+    Disconnected,
+    Connecting,
+    Connected,
+}
+
+/// Command run before connecting to an application (eg: a port-forward),
+/// the application is connected to once the port of its url is opened
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub(crate) struct PreConnectHook {
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Seconds given to the command to open the port
+    #[serde(default = "PreConnectHook::default_ready_timeout")]
+    pub ready_timeout: u64,
+}
+
+impl PreConnectHook {
+    fn default_ready_timeout() -> u64 {
+        30
+    }
 }
 
 /// Application tracked by the application
@@ -25,6 +73,14 @@ pub(crate) struct Application {
     title: String,
     url: Url,
     state: ApplicationState,
+    /// Port of the proxy re-exposing the application to other consoles
+    #[serde(default)]
+    proxy_port: Option<u16>,
+    /// Exit code of the process, if launched by the application and exited
+    #[serde(default)]
+    exit_code: Option<i32>,
+    #[serde(default)]
+    pre_connect: Option<PreConnectHook>,
 
     #[serde(skip)]
     connection: Option<Connection>,
@@ -37,16 +93,27 @@ impl Application {
             title,
             url,
             state: ApplicationState::Disabled,
+            proxy_port: None,
+            exit_code: None,
+            pre_connect: None,
 
             connection: None,
         }
     }
 
+    /// Creates a read-only application, whose data is imported
+    pub fn new_offline(title: String, url: Url) -> Application {
+        Application {
+            state: ApplicationState::Offline,
+            ..Self::new(title, url)
+        }
+    }
+
     pub fn id(&self) -> &Uuid {
         &self.id
     }
 
-    pub fn _title(&self) -> &str {
+    pub fn title(&self) -> &str {
         &self.title
     }
 
@@ -58,6 +125,26 @@ impl Application {
         self.state
     }
 
+    pub fn pre_connect(&self) -> Option<&PreConnectHook> {
+        self.pre_connect.as_ref()
+    }
+
+    pub fn set_pre_connect(&mut self, pre_connect: Option<PreConnectHook>) {
+        self.pre_connect = pre_connect;
+    }
+
+    pub fn proxy_port(&self) -> Option<u16> {
+        self.proxy_port
+    }
+
+    pub fn set_proxy_port(&mut self, proxy_port: Option<u16>) {
+        self.proxy_port = proxy_port;
+    }
+
+    pub fn connection(&self) -> Option<&Connection> {
+        self.connection.as_ref()
+    }
+
     // vreau sa vad info pentru aplicatia asta
     pub fn enable(&mut self, connection: Connection) {
         if self.state == ApplicationState::Disabled {
@@ -66,6 +153,15 @@ impl Application {
         }
     }
 
+    /// Marks the process of a launched application as ended
+    pub async fn exit(&mut self, exit_code: Option<i32>) {
+        if let Some(connection) = self.connection.take() {
+            connection.commands.send(Command::Disconnect).await.ok();
+        }
+        self.state = ApplicationState::Exited;
+        self.exit_code = exit_code;
+    }
+
     pub async fn disable(&mut self) {
         if let Some(connection) = self.connection.take() {
             connection.commands.send(Command::Disconnect).await.ok();
@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AsyncOp {
+    pub app_id: Uuid,
+    pub id: u64,
+    /// Method which created the async op (eg: `Mutex::lock`)
+    pub source: String,
+    pub resource_id: Option<u64>,
+    pub parent_id: Option<u64>,
+    /// Task awaiting this async op
+    pub task_id: Option<u64>,
+    pub created_at: Option<SystemTime>,
+    pub polls: u64,
+    /// Time (in the application's clock) since the op is waiting to become
+    /// ready, `None` if its last poll returned ready
+    pub pending_since: Option<SystemTime>,
+}
+
+impl AsyncOp {
+    pub fn id(&self) -> String {
+        format!("{}.{}", self.app_id, self.id)
+    }
+}
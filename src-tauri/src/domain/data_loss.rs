@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    time::{Duration, SystemTime},
+};
+
+/// Drop bursts kept for every application, the oldest are discarded first
+const MAX_DROP_BURSTS: usize = 100;
+
+/// Time after the last dropped event during which the data is considered incomplete
+const INCOMPLETE_PERIOD: Duration = Duration::from_secs(60);
+
+/// Number of events an application could not record, as its event buffer was full
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct DroppedEvents {
+    pub tasks: u64,
+    pub resources: u64,
+    pub async_ops: u64,
+}
+
+impl DroppedEvents {
+    pub fn total(&self) -> u64 {
+        self.tasks + self.resources + self.async_ops
+    }
+
+    fn add(&mut self, other: DroppedEvents) {
+        self.tasks += other.tasks;
+        self.resources += other.resources;
+        self.async_ops += other.async_ops;
+    }
+}
+
+/// Successive updates of an application reporting dropped events
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DropBurst {
+    /// Time of the first and last updates reporting dropped events (in the application's clock)
+    pub started_at: SystemTime,
+    pub ended_at: SystemTime,
+    pub dropped: DroppedEvents,
+}
+
+/// Data the application could not send since connected, the statistics
+/// shown for it may be wrong while it is incomplete
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DataLoss {
+    pub dropped: DroppedEvents,
+    /// Set while events were dropped recently
+    pub incomplete: bool,
+    pub bursts: VecDeque<DropBurst>,
+    /// Set while the latest updates report dropped events
+    #[serde(skip)]
+    dropping: bool,
+}
+
+impl DataLoss {
+    /// Accounts the events dropped since the previous update of the application
+    pub fn record(&mut self, now: SystemTime, dropped: DroppedEvents) {
+        if dropped.total() == 0 {
+            self.dropping = false;
+        } else {
+            self.dropped.add(dropped);
+            match self.bursts.back_mut() {
+                Some(burst) if self.dropping => {
+                    burst.ended_at = now;
+                    burst.dropped.add(dropped);
+                }
+                _ => {
+                    if self.bursts.len() == MAX_DROP_BURSTS {
+                        self.bursts.pop_front();
+                    }
+                    self.bursts.push_back(DropBurst {
+                        started_at: now,
+                        ended_at: now,
+                        dropped,
+                    });
+                }
+            }
+            self.dropping = true;
+        }
+
+        self.incomplete = self.bursts.back().is_some_and(|burst| {
+            now.duration_since(burst.ended_at)
+                .map_or(true, |elapsed| elapsed < INCOMPLETE_PERIOD)
+        });
+    }
+
+    /// Counts the dropped events from zero on a new connection, the bursts are kept
+    pub fn reconnected(&mut self) {
+        self.dropped = DroppedEvents::default();
+        self.dropping = false;
+    }
+}
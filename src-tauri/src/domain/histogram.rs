@@ -0,0 +1,122 @@
+use hdrhistogram::Histogram;
+use log::warn;
+use serde::Serialize;
+use std::time::Duration;
+
+/// Number of buckets returned for plotting a histogram
+const PLOT_BUCKETS: u64 = 50;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct HistogramBucket {
+    /// Highest duration counted in the bucket
+    pub upper_bound: Duration,
+    pub count: u64,
+}
+
+/// Distribution of durations (eg: poll times) recorded by the instrumented
+/// application, with its most relevant percentiles
+#[derive(Serialize, Clone, Debug)]
+pub struct DurationHistogram {
+    pub count: u64,
+    pub min: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+    /// Number of values exceeding the histogram's maximum value
+    pub high_outliers: u64,
+    pub highest_outlier: Option<Duration>,
+    pub buckets: Vec<HistogramBucket>,
+
+    // Values recorded in nanoseconds, kept for merging
+    #[serde(skip)]
+    raw: Histogram<u64>,
+}
+
+impl DurationHistogram {
+    pub fn new(raw: Histogram<u64>, high_outliers: u64, highest_outlier: Option<u64>) -> Self {
+        let step = (raw.max() / PLOT_BUCKETS).max(1);
+        let buckets = if raw.is_empty() {
+            Vec::new()
+        } else {
+            raw.iter_linear(step)
+                .map(|value| HistogramBucket {
+                    upper_bound: Duration::from_nanos(value.value_iterated_to()),
+                    count: value.count_since_last_iteration(),
+                })
+                .collect()
+        };
+
+        Self {
+            count: raw.len(),
+            min: Duration::from_nanos(raw.min()),
+            p50: Duration::from_nanos(raw.value_at_quantile(0.5)),
+            p90: Duration::from_nanos(raw.value_at_quantile(0.9)),
+            p99: Duration::from_nanos(raw.value_at_quantile(0.99)),
+            max: Duration::from_nanos(highest_outlier.unwrap_or(raw.max())),
+            high_outliers,
+            highest_outlier: highest_outlier.map(Duration::from_nanos),
+            buckets,
+            raw,
+        }
+    }
+
+    /// Number of values longer than the threshold, including the outliers
+    pub fn count_above(&self, threshold: Duration) -> u64 {
+        let threshold = threshold.as_nanos().min(u64::MAX as u128) as u64;
+        let below = self.raw.count_between(0, threshold);
+        self.raw.len().saturating_sub(below) + self.high_outliers
+    }
+
+    /// Merges several histograms (eg: of all the tasks spawned at the same location)
+    pub fn merge<'a>(histograms: impl IntoIterator<Item = &'a DurationHistogram>) -> Option<Self> {
+        let mut histograms = histograms.into_iter().peekable();
+        histograms.peek()?;
+
+        // Auto resizing, so that histograms with different ranges can be added
+        let mut raw = Histogram::<u64>::new(3).ok()?;
+        let mut high_outliers = 0;
+        let mut highest_outlier = None;
+        for histogram in histograms {
+            if let Err(error) = raw.add(&histogram.raw) {
+                warn!("Skipped a histogram which cannot be merged ({error:?})");
+                continue;
+            }
+            high_outliers += histogram.high_outliers;
+            highest_outlier = highest_outlier.max(histogram.highest_outlier);
+        }
+
+        Some(Self::new(
+            raw,
+            high_outliers,
+            highest_outlier.map(|outlier| outlier.as_nanos() as u64),
+        ))
+    }
+}
+
+/// Histograms reported in the details of a task
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct TaskHistograms {
+    /// Duration of the task's polls
+    pub poll_times: Option<DurationHistogram>,
+    /// Time between the task being woken and being polled
+    pub scheduled_times: Option<DurationHistogram>,
+}
+
+impl TaskHistograms {
+    pub fn merge<'a>(histograms: impl IntoIterator<Item = &'a TaskHistograms> + Clone) -> Self {
+        Self {
+            poll_times: DurationHistogram::merge(
+                histograms
+                    .clone()
+                    .into_iter()
+                    .filter_map(|histograms| histograms.poll_times.as_ref()),
+            ),
+            scheduled_times: DurationHistogram::merge(
+                histograms
+                    .into_iter()
+                    .filter_map(|histograms| histograms.scheduled_times.as_ref()),
+            ),
+        }
+    }
+}
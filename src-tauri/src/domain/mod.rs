@@ -1,7 +1,16 @@
 //! Module defining all data objects
 
 pub(crate) mod application;
+pub(crate) mod async_op;
+pub(crate) mod data_loss;
+pub(crate) mod histogram;
+pub(crate) mod resource;
+pub(crate) mod secrets;
+pub(crate) mod settings;
 pub(crate) mod storable;
 pub(crate) mod task;
 
+pub use async_op::*;
+pub use histogram::*;
+pub use resource::*;
 pub use task::*;
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// State attribute of a resource or of an async op (eg: the permits of a semaphore)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Attribute {
+    pub name: String,
+    pub value: String,
+    pub unit: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Resource {
+    pub app_id: Uuid,
+    pub id: u64,
+    pub kind: String,
+    pub concrete_type: String,
+    pub location: Option<String>,
+    pub parent_id: Option<u64>,
+    pub is_internal: bool,
+    pub attributes: Vec<Attribute>,
+    /// Last task that completed an async op on this resource
+    pub holder_task_id: Option<u64>,
+}
+
+impl Resource {
+    pub fn id(&self) -> String {
+        format!("{}.{}", self.app_id, self.id)
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.name == name)
+            .map(|attribute| attribute.value.as_str())
+    }
+
+    /// Tells if the resource is currently acquired, based on the state
+    /// attributes reported by tokio's synchronization primitives
+    /// (`Mutex`, `RwLock` and `Semaphore`)
+    ///
+    /// Resources which do not report such attributes (timers, channels, io)
+    /// are never considered held.
+    pub fn is_held(&self) -> bool {
+        self.attribute("locked") == Some("true")
+            || self.attribute("write_locked") == Some("true")
+            || self.attribute("permits") == Some("0")
+    }
+}
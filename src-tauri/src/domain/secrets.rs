@@ -0,0 +1,39 @@
+use super::storable::Storable;
+use crate::error::Error as TraceError;
+use crate::mappers::read_file;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt};
+use uuid::Uuid;
+
+/// Sensitive data of the applications, stored apart from the applications
+/// in a file only readable by the user
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct Secrets {
+    /// gRPC metadata (eg: `authorization`) sent with every request to the application
+    pub metadata: HashMap<Uuid, HashMap<String, String>>,
+}
+
+#[async_trait]
+impl Storable<Secrets> for Secrets {
+    const FILE_EXTENSION: &str = "secrets.json";
+
+    async fn load_all(path: String) -> Result<Secrets, TraceError> {
+        let secrets =
+            serde_json::from_str(&read_file(&format!("{}/{}", path, Self::FILE_EXTENSION)).await?)?;
+
+        Ok(secrets)
+    }
+}
+
+/// gRPC metadata of an application, only the names are debug printed
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(transparent)]
+pub struct Metadata(pub HashMap<String, String>);
+
+impl fmt::Debug for Metadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
@@ -0,0 +1,190 @@
+use super::storable::Storable;
+use crate::error::Error as TraceError;
+use crate::mappers::read_file;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Ports the discovery can probe, over all the ranges
+const MAX_DISCOVERY_PORTS: usize = 1024;
+
+/// Local HTTP endpoint exporting the metrics of the applications
+/// in the Prometheus text format
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct MetricsEndpointSettings {
+    pub enabled: bool,
+    /// Port listened on the loopback interface
+    pub port: u16,
+}
+
+impl Default for MetricsEndpointSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 9464,
+        }
+    }
+}
+
+/// Local REST and WebSocket API, requests must carry the token
+/// (`Authorization: Bearer <token>` header or `token` query parameter)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ApiSettings {
+    pub enabled: bool,
+    /// Port listened on the loopback interface
+    pub port: u16,
+    pub token: String,
+    /// Accept the applications added with a pre-connect command, which runs
+    /// a program on the machine
+    pub allow_pre_connect: bool,
+}
+
+impl Default for ApiSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 6670,
+            token: Uuid::new_v4().simple().to_string(),
+            allow_pre_connect: false,
+        }
+    }
+}
+
+/// Inclusive range of ports
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+/// Search of the console-subscriber servers running on the machine
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct DiscoverySettings {
+    /// Ports probed on the loopback interface, in addition to the ports
+    /// the processes are seen listening on
+    pub port_ranges: Vec<PortRange>,
+}
+
+impl Default for DiscoverySettings {
+    fn default() -> Self {
+        Self {
+            // console-subscriber listens on 6669 by default
+            port_ranges: vec![PortRange {
+                start: 6669,
+                end: 6679,
+            }],
+        }
+    }
+}
+
+impl DiscoverySettings {
+    /// # Error
+    ///
+    /// If a range is empty, includes the port 0 or if the ranges hold more
+    /// than `MAX_DISCOVERY_PORTS` ports
+    pub fn validate(&self) -> Result<(), TraceError> {
+        let mut ports = 0;
+        for range in &self.port_ranges {
+            if range.start == 0 || range.start > range.end {
+                return Err(TraceError::InvalidSettings(format!(
+                    "invalid discovery port range {}-{}",
+                    range.start, range.end
+                )));
+            }
+            ports += usize::from(range.end - range.start) + 1;
+        }
+        if ports > MAX_DISCOVERY_PORTS {
+            return Err(TraceError::InvalidSettings(format!(
+                "the discovery port ranges hold {ports} ports, at most {MAX_DISCOVERY_PORTS} can be probed"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Limits of the completed tasks kept in the storage, unset limits are not applied
+///
+/// Tasks which are still running are always kept
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct RetentionSettings {
+    /// Seconds after which completed tasks are removed
+    pub max_age: Option<u64>,
+    /// Number of completed tasks kept for every application, the latest are kept
+    pub max_tasks_per_app: Option<usize>,
+    /// Approximate size of all the stored tasks, in bytes
+    pub max_total_size: Option<u64>,
+}
+
+impl Default for RetentionSettings {
+    fn default() -> Self {
+        Self {
+            max_age: Some(24 * 60 * 60),
+            max_tasks_per_app: Some(10_000),
+            max_total_size: Some(64 * 1024 * 1024),
+        }
+    }
+}
+
+/// Thresholds of the task leak detection
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct LeakDetectionSettings {
+    /// Seconds a live task can stay idle before being reported
+    pub idle_threshold: u64,
+    /// Seconds over which the live tasks of a location must keep growing
+    pub window: u64,
+    /// Live tasks a location must gain over the window to be reported
+    pub min_growth: usize,
+}
+
+impl Default for LeakDetectionSettings {
+    fn default() -> Self {
+        Self {
+            idle_threshold: 10 * 60,
+            window: 5 * 60,
+            min_growth: 10,
+        }
+    }
+}
+
+/// Detection of the polls blocking their worker thread
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct BlockingDetectionSettings {
+    /// Milliseconds a poll can last before being reported
+    pub poll_budget: u64,
+}
+
+impl Default for BlockingDetectionSettings {
+    fn default() -> Self {
+        Self { poll_budget: 10 }
+    }
+}
+
+/// User preferences, kept across restarts
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    pub metrics_endpoint: MetricsEndpointSettings,
+    pub api: ApiSettings,
+    pub discovery: DiscoverySettings,
+    pub retention: RetentionSettings,
+    pub leak_detection: LeakDetectionSettings,
+    pub blocking_detection: BlockingDetectionSettings,
+}
+
+#[async_trait]
+impl Storable<Settings> for Settings {
+    const FILE_EXTENSION: &str = "settings.json";
+
+    async fn load_all(path: String) -> Result<Settings, TraceError> {
+        let settings =
+            serde_json::from_str(&read_file(&format!("{}/{}", path, Self::FILE_EXTENSION)).await?)?;
+
+        Ok(settings)
+    }
+}
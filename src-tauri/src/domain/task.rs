@@ -3,9 +3,61 @@ use crate::error::Error as TraceError;
 use crate::mappers::read_file;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
 use uuid::Uuid;
 
+/// Latest statistics reported for a task, all counters are cumulative
+/// since the task was spawned
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TaskStats {
+    pub created_at: Option<SystemTime>,
+    pub dropped_at: Option<SystemTime>,
+    pub wakes: u64,
+    pub waker_clones: u64,
+    pub waker_drops: u64,
+    pub self_wakes: u64,
+    pub last_wake: Option<SystemTime>,
+    pub polls: u64,
+    pub first_poll: Option<SystemTime>,
+    pub last_poll_started: Option<SystemTime>,
+    pub last_poll_ended: Option<SystemTime>,
+    pub busy_time: Duration,
+    pub scheduled_time: Duration,
+}
+
+/// A poll of a task, as observed from its successive stats
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct PollSpan {
+    pub started: SystemTime,
+    pub ended: SystemTime,
+}
+
+/// State of a task, derived from its latest stats
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum TaskState {
+    /// Currently being polled
+    Running,
+    /// Woken, waiting to be polled
+    Scheduled,
+    /// Waiting to be woken
+    Idle,
+    Completed,
+}
+
+impl TaskState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskState::Running => "running",
+            TaskState::Scheduled => "scheduled",
+            TaskState::Idle => "idle",
+            TaskState::Completed => "completed",
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Task {
     pub app_id: Uuid,
@@ -13,12 +65,30 @@ pub struct Task {
     pub tid: Option<u64>,
     pub name: Option<String>,
     pub kind: Option<String>,
+    /// Location in code where the task was spawned
+    #[serde(default)]
+    pub location: Option<String>,
+    #[serde(default)]
+    pub stats: TaskStats,
 }
 
 impl Task {
     pub fn id(&self) -> String {
         format!("{}.{}", self.app_id, self.id)
     }
+
+    pub fn state(&self) -> TaskState {
+        let stats = &self.stats;
+        if stats.dropped_at.is_some() {
+            TaskState::Completed
+        } else if stats.last_poll_started > stats.last_poll_ended {
+            TaskState::Running
+        } else if stats.last_wake > stats.last_poll_ended {
+            TaskState::Scheduled
+        } else {
+            TaskState::Idle
+        }
+    }
 }
 
 #[async_trait]
@@ -0,0 +1,207 @@
+//! REST and WebSocket API offering the operations of the user interface
+//!
+//! | Route                                | Operation                          |
+//! |--------------------------------------|------------------------------------|
+//! | `GET /api/applications`              | lists the applications             |
+//! | `POST /api/applications`             | adds an application (title, url)   |
+//! | `DELETE /api/applications/:id`       | deletes an application             |
+//! | `POST /api/applications/:id/disable` | disables an application            |
+//! | `POST /api/tasks/query`              | queries the tasks (see `TaskQuery`)|
+//! | `GET /api/updates`                   | WebSocket stream of live updates   |
+//!
+//! The live updates are the events sent to the user interface, as JSON
+//! messages, eg: `{"event":"update:tasks","payload":[...]}`
+
+use crate::domain::application::{Application, PreConnectHook};
+use crate::error::{Error as TraceError, ErrorCategory};
+use crate::query::{TaskPage, TaskQuery};
+use crate::state_manager::StateManager;
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Request, State,
+    },
+    http::{header::AUTHORIZATION, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::json;
+use std::{collections::HashMap, sync::Arc};
+use subtle::ConstantTimeEq;
+use tokio::sync::broadcast::error::RecvError;
+use url::Url;
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+struct NewApplication {
+    title: String,
+    url: Url,
+    #[serde(default)]
+    pre_connect: Option<PreConnectHook>,
+    /// gRPC metadata sent to the application (eg: `authorization`)
+    #[serde(default)]
+    metadata: HashMap<String, String>,
+}
+
+pub(crate) fn router(state_manager: Arc<StateManager>, token: String) -> Router {
+    Router::new()
+        .route(
+            "/api/applications",
+            get(list_applications).post(add_application),
+        )
+        .route("/api/applications/:id", delete(delete_application))
+        .route("/api/applications/:id/disable", post(disable_application))
+        .route("/api/tasks/query", post(query_tasks))
+        .route("/api/updates", get(live_updates))
+        .route_layer(middleware::from_fn_with_state(
+            Arc::<str>::from(token),
+            authorize,
+        ))
+        .with_state(state_manager)
+}
+
+impl IntoResponse for TraceError {
+    fn into_response(self) -> Response {
+        let status = match (&self, self.category()) {
+            (TraceError::ApplicationNotFound(_), _) => StatusCode::NOT_FOUND,
+            (TraceError::ApplicationAlreadyConnected(_), _) => StatusCode::CONFLICT,
+            (TraceError::PreConnectNotAllowed, _) => StatusCode::FORBIDDEN,
+            (_, ErrorCategory::Validation) => StatusCode::BAD_REQUEST,
+            (_, ErrorCategory::Network | ErrorCategory::Protocol) => StatusCode::BAD_GATEWAY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        (status, Json(json!({ "error": self }))).into_response()
+    }
+}
+
+/// Rejects the requests which do not carry the token, either as a bearer
+/// token or as the `token` query parameter (browsers cannot set the headers
+/// of a WebSocket)
+async fn authorize(State(token): State<Arc<str>>, request: Request, next: Next) -> Response {
+    let header_token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let query_token = request.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .find_map(|parameter| parameter.strip_prefix("token="))
+    });
+
+    match header_token.or(query_token) {
+        // Compared in constant time, so that the token cannot be guessed from the response times
+        Some(request_token) if bool::from(request_token.as_bytes().ct_eq(token.as_bytes())) => {
+            next.run(request).await
+        }
+        _ => {
+            warn!(
+                "Rejected API request to {} without a valid token",
+                request.uri().path()
+            );
+            StatusCode::UNAUTHORIZED.into_response()
+        }
+    }
+}
+
+async fn list_applications(
+    State(state_manager): State<Arc<StateManager>>,
+) -> Json<Vec<Arc<Application>>> {
+    Json(state_manager.current_applications().await)
+}
+
+async fn add_application(
+    State(state_manager): State<Arc<StateManager>>,
+    Json(application): Json<NewApplication>,
+) -> Result<Json<Uuid>, TraceError> {
+    info!(
+        "Received API request to add application with title {} and url {}",
+        application.title, application.url
+    );
+    // Pre-connect commands run programs, the token alone does not allow them
+    if application.pre_connect.is_some() && !state_manager.settings().await?.api.allow_pre_connect {
+        warn!(
+            "Rejected API request to add application with url {} and a pre-connect command",
+            application.url
+        );
+        return Err(TraceError::PreConnectNotAllowed);
+    }
+    state_manager
+        .add_application(
+            application.title,
+            application.url,
+            application.pre_connect,
+            application.metadata,
+        )
+        .await
+        .map(Json)
+}
+
+async fn delete_application(
+    State(state_manager): State<Arc<StateManager>>,
+    Path(uuid): Path<Uuid>,
+) -> StatusCode {
+    state_manager.delete_connection(uuid).await;
+    StatusCode::NO_CONTENT
+}
+
+async fn disable_application(
+    State(state_manager): State<Arc<StateManager>>,
+    Path(uuid): Path<Uuid>,
+) -> Result<StatusCode, TraceError> {
+    state_manager.disable_application(uuid).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn query_tasks(
+    State(state_manager): State<Arc<StateManager>>,
+    Json(query): Json<TaskQuery>,
+) -> Result<Json<TaskPage>, TraceError> {
+    state_manager.query_tasks(&query).await.map(Json)
+}
+
+async fn live_updates(
+    State(state_manager): State<Arc<StateManager>>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    upgrade.on_upgrade(move |socket| forward_live_updates(state_manager, socket))
+}
+
+/// Sends the live updates until the client disconnects
+async fn forward_live_updates(state_manager: Arc<StateManager>, mut socket: WebSocket) {
+    let mut updates = state_manager.subscribe_live_updates();
+
+    loop {
+        tokio::select! {
+            update = updates.recv() => {
+                let update = match update {
+                    Ok(update) => update,
+                    // Slow clients miss updates, the next ones are complete snapshots
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("API client lagging behind, skipped {skipped} updates");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                let Ok(message) = serde_json::to_string(&update) else {
+                    continue;
+                };
+                if socket.send(Message::Text(message)).await.is_err() {
+                    break;
+                }
+            }
+            message = socket.recv() => {
+                match message {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    // Nothing is expected from the clients
+                    Some(Ok(_)) => {}
+                }
+            }
+        }
+    }
+}
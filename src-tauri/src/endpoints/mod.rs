@@ -0,0 +1,83 @@
+//! Local HTTP endpoints exposing the state of the debugger to other tools
+
+pub(crate) mod api;
+pub(crate) mod prometheus;
+
+use crate::error::Error as TraceError;
+use axum::Router;
+use log::{error, info};
+use std::{
+    future::Future,
+    net::{Ipv4Addr, SocketAddr},
+};
+use tokio::{net::TcpListener, sync::oneshot};
+
+/// Server listening on the loopback interface, stopped once dropped
+pub(crate) struct LocalServer {
+    address: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl LocalServer {
+    /// Starts serving the routes on the given port
+    ///
+    /// # Error
+    ///
+    /// If the port cannot be listened on (eg: already used by another
+    /// process), an error will be returned
+    pub async fn start(port: u16, router: Router) -> Result<Self, TraceError> {
+        Self::start_with(port, |listener, shutdown| async move {
+            axum::serve(listener, router)
+                .with_graceful_shutdown(async {
+                    shutdown.await.ok();
+                })
+                .await
+                .map_err(anyhow::Error::from)
+        })
+        .await
+    }
+
+    /// Starts a server on the given port, `serve` runs it until the
+    /// shutdown receiver completes
+    pub async fn start_with<F, Fut>(port: u16, serve: F) -> Result<Self, TraceError>
+    where
+        F: FnOnce(TcpListener, oneshot::Receiver<()>) -> Fut,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+        let listener =
+            TcpListener::bind(address)
+                .await
+                .map_err(|error| TraceError::CannotListen {
+                    error: error.into(),
+                    address: address.to_string(),
+                })?;
+        info!("Listening on {address}");
+
+        let (shutdown, shutdown_receiver) = oneshot::channel();
+        let server = serve(listener, shutdown_receiver);
+        tokio::spawn(async move {
+            if let Err(error) = server.await {
+                error!("Server listening on {address} stopped due to {error:?}");
+            }
+        });
+
+        Ok(Self {
+            address,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+}
+
+impl Drop for LocalServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            info!("Stopping server listening on {}", self.address);
+            shutdown.send(()).ok();
+        }
+    }
+}
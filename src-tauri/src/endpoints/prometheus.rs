@@ -0,0 +1,199 @@
+//! Export of the applications metrics in the Prometheus text format
+//!
+//! Every sample is labelled with the id and title of its application,
+//! eg: `tokio_display_live_tasks{app_id="...",app="server",kind="task"} 12`
+
+use crate::domain::application::{ApplicationState, ConnectionStatus};
+use crate::state_manager::{state::State as AppState, StateManager};
+use axum::{
+    extract::State, http::header::CONTENT_TYPE, response::IntoResponse, routing::get, Router,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{Display, Write},
+    sync::Arc,
+    time::Duration,
+};
+use uuid::Uuid;
+
+const CONTENT_TYPE_TEXT: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+pub(crate) fn router(state_manager: Arc<StateManager>) -> Router {
+    Router::new()
+        .route("/metrics", get(metrics))
+        .with_state(state_manager)
+}
+
+async fn metrics(State(state_manager): State<Arc<StateManager>>) -> impl IntoResponse {
+    (
+        [(CONTENT_TYPE, CONTENT_TYPE_TEXT)],
+        render(&state_manager.state).await,
+    )
+}
+
+/// Totals of the tasks of an application
+#[derive(Default)]
+struct TaskTotals {
+    live_by_kind: BTreeMap<String, u64>,
+    polls: u64,
+    busy_time: Duration,
+}
+
+/// Writes the samples of a metric, preceded by its description
+fn write_metric<V: Display>(
+    output: &mut String,
+    name: &str,
+    kind: &str,
+    help: &str,
+    samples: impl IntoIterator<Item = (String, V)>,
+) {
+    writeln!(output, "# HELP {name} {help}").ok();
+    writeln!(output, "# TYPE {name} {kind}").ok();
+    for (labels, value) in samples {
+        writeln!(output, "{name}{{{labels}}} {value}").ok();
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Renders the metrics of every registered application
+pub(crate) async fn render(state: &AppState) -> String {
+    let mut applications = state.get_current_applications_list().await;
+    applications.sort_by_key(|application| *application.id());
+    let connection_statuses = state.get_connection_statuses().await;
+    let data_loss = state.get_data_loss().await;
+
+    // Polls of the removed tasks are kept, so that the counters never decrease
+    let mut totals: HashMap<Uuid, TaskTotals> = state
+        .get_removed_tasks()
+        .await
+        .into_iter()
+        .map(|(app_id, removed)| {
+            let app_totals = TaskTotals {
+                polls: removed.polls,
+                busy_time: removed.busy_time,
+                ..Default::default()
+            };
+            (app_id, app_totals)
+        })
+        .collect();
+    for task in state.get_tasks_snapshot().await.values() {
+        let app_totals = totals.entry(task.app_id).or_default();
+        app_totals.polls += task.stats.polls;
+        app_totals.busy_time += task.stats.busy_time;
+        if task.stats.dropped_at.is_none() {
+            let kind = task.kind.clone().unwrap_or_else(|| "unknown".to_owned());
+            *app_totals.live_by_kind.entry(kind).or_default() += 1;
+        }
+    }
+
+    let labels: Vec<(Uuid, String)> = applications
+        .iter()
+        .map(|application| {
+            (
+                *application.id(),
+                format!(
+                    "app_id=\"{}\",app=\"{}\"",
+                    application.id(),
+                    escape_label(application.title())
+                ),
+            )
+        })
+        .collect();
+    let no_totals = TaskTotals::default();
+    let totals_of = |app_id: &Uuid| totals.get(app_id).unwrap_or(&no_totals);
+
+    let mut output = String::new();
+    write_metric(
+        &mut output,
+        "tokio_display_application_enabled",
+        "gauge",
+        "Whether updates are received from the application (1) or not (0)",
+        applications
+            .iter()
+            .zip(&labels)
+            .map(|(application, (_, labels))| {
+                let enabled = application.state() != ApplicationState::Disabled;
+                (labels.clone(), enabled as u8)
+            }),
+    );
+    write_metric(
+        &mut output,
+        "tokio_display_application_connected",
+        "gauge",
+        "Whether the application is currently connected (1) or not (0)",
+        labels.iter().map(|(app_id, labels)| {
+            let connected = connection_statuses.get(app_id) == Some(&ConnectionStatus::Connected);
+            (labels.clone(), connected as u8)
+        }),
+    );
+    write_metric(
+        &mut output,
+        "tokio_display_live_tasks",
+        "gauge",
+        "Number of tasks that are not completed, by kind",
+        labels.iter().flat_map(|(app_id, labels)| {
+            totals_of(app_id)
+                .live_by_kind
+                .iter()
+                .map(move |(kind, count)| {
+                    (format!("{labels},kind=\"{}\"", escape_label(kind)), *count)
+                })
+        }),
+    );
+    write_metric(
+        &mut output,
+        "tokio_display_task_polls_total",
+        "counter",
+        "Number of polls of the tasks of the application, including the removed ones",
+        labels
+            .iter()
+            .map(|(app_id, labels)| (labels.clone(), totals_of(app_id).polls)),
+    );
+    write_metric(
+        &mut output,
+        "tokio_display_task_busy_seconds_total",
+        "counter",
+        "Time spent polling the tasks of the application, including the removed ones",
+        labels
+            .iter()
+            .map(|(app_id, labels)| (labels.clone(), totals_of(app_id).busy_time.as_secs_f64())),
+    );
+    write_metric(
+        &mut output,
+        "tokio_display_dropped_events_total",
+        "counter",
+        "Number of events the application could not send since connected, by kind",
+        labels.iter().flat_map(|(app_id, labels)| {
+            let dropped = data_loss
+                .get(app_id)
+                .map(|data_loss| data_loss.dropped)
+                .unwrap_or_default();
+            [
+                ("task", dropped.tasks),
+                ("resource", dropped.resources),
+                ("async_op", dropped.async_ops),
+            ]
+            .map(|(kind, count)| (format!("{labels},kind=\"{kind}\""), count))
+        }),
+    );
+    write_metric(
+        &mut output,
+        "tokio_display_data_incomplete",
+        "gauge",
+        "Whether the application dropped events recently (1) or not (0)",
+        labels.iter().map(|(app_id, labels)| {
+            let incomplete = data_loss
+                .get(app_id)
+                .is_some_and(|data_loss| data_loss.incomplete);
+            (labels.clone(), incomplete as u8)
+        }),
+    );
+
+    output
+}
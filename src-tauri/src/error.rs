@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use uuid::Uuid;
 
 #[derive(Debug, thiserror::Error)]
@@ -6,21 +8,204 @@ pub enum Error {
     Url(#[from] url::ParseError),
     #[error("Application with id {0} is already connected")]
     ApplicationAlreadyConnected(Uuid),
-    #[error("TODO: add message for me")]
+    #[error("Application with id {0} not found")]
+    ApplicationNotFound(Uuid),
+    #[error("Unexpected error: {0}")]
     Anyhow(#[from] anyhow::Error),
     #[error("Path {0} not found")]
     PathNotFound(String),
+    #[error("Invalid path: {0}")]
+    InvalidPath(String),
     #[error("Serde error encountered: {0}")]
     Serde(#[from] serde_json::Error),
     #[error("Cannot create the storage directory at path {path} due to {error}")]
     CannotCreateStorage { error: anyhow::Error, path: String },
+    #[error("Cannot write {path} due to {error}")]
+    CannotWrite { error: anyhow::Error, path: String },
+    #[error("Invalid recording: {0}")]
+    InvalidRecording(String),
+    #[error("Invalid query: {0}")]
+    InvalidQuery(String),
+    #[error("Invalid settings: {0}")]
+    InvalidSettings(String),
+    #[error("Invalid gRPC metadata {0}")]
+    InvalidMetadata(String),
+    #[error("Invalid endpoint {url}: {error}")]
+    InvalidEndpoint { url: String, error: String },
+    #[error("Cannot connect to {url}: {error}")]
+    CannotConnect { url: String, error: String },
+    #[error("The application answered with {code}: {message}")]
+    Protocol { code: String, message: String },
+    #[error("Cannot launch {program} due to {error}")]
+    CannotLaunch {
+        error: anyhow::Error,
+        program: String,
+    },
+    #[error("Pre-connect command {program} {reason}: {stderr}")]
+    PreConnectFailed {
+        program: String,
+        reason: String,
+        stderr: String,
+    },
+    #[error("Pre-connect commands are not accepted from the API unless allowed in the settings")]
+    PreConnectNotAllowed,
+    #[error("Collector: {0}")]
+    Collector(String),
+    /// Error which happened in the collector, as reported to the window
+    #[error("{}", .0.message)]
+    Forwarded(ErrorReport),
+    #[error("Cannot listen on {address} due to {error}")]
+    CannotListen {
+        error: anyhow::Error,
+        address: String,
+    },
 }
 
-impl serde::Serialize for Error {
+/// Broad cause of an error, to let the frontend decide how to present it
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    Network,
+    Protocol,
+    Storage,
+    Validation,
+    /// Programs run by the application (eg: launched or pre-connect commands)
+    Process,
+    Internal,
+}
+
+/// Serializable form of an error, as sent to the frontend
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ErrorReport {
+    /// Stable identifier of the error, eg: `application_not_found`
+    pub code: String,
+    pub category: ErrorCategory,
+    pub message: String,
+    /// Values the error is about (eg: the url of the application)
+    pub details: Value,
+}
+
+impl Error {
+    /// Failure to open a connection to an application
+    pub fn connection(url: &str, error: &(dyn std::error::Error + 'static)) -> Self {
+        // Transport errors only describe the failure in their sources
+        let mut messages = vec![error.to_string()];
+        let mut source = error.source();
+        while let Some(error) = source {
+            messages.push(error.to_string());
+            source = error.source();
+        }
+
+        Error::CannotConnect {
+            url: url.to_owned(),
+            error: messages.join(": "),
+        }
+    }
+
+    /// Stable identifier of the error, not changed when the message is
+    pub fn code(&self) -> &str {
+        match self {
+            Error::Url(_) => "invalid_url",
+            Error::ApplicationAlreadyConnected(_) => "application_already_connected",
+            Error::ApplicationNotFound(_) => "application_not_found",
+            Error::Anyhow(_) => "unexpected",
+            Error::PathNotFound(_) => "path_not_found",
+            Error::InvalidPath(_) => "invalid_path",
+            Error::Serde(_) => "serialization",
+            Error::CannotCreateStorage { .. } => "cannot_create_storage",
+            Error::CannotWrite { .. } => "cannot_write",
+            Error::InvalidRecording(_) => "invalid_recording",
+            Error::InvalidQuery(_) => "invalid_query",
+            Error::InvalidSettings(_) => "invalid_settings",
+            Error::InvalidMetadata(_) => "invalid_metadata",
+            Error::InvalidEndpoint { .. } => "invalid_endpoint",
+            Error::CannotConnect { .. } => "cannot_connect",
+            Error::Protocol { .. } => "protocol",
+            Error::CannotLaunch { .. } => "cannot_launch",
+            Error::PreConnectFailed { .. } => "pre_connect_failed",
+            Error::PreConnectNotAllowed => "pre_connect_not_allowed",
+            Error::Collector(_) => "collector",
+            Error::Forwarded(report) => &report.code,
+            Error::CannotListen { .. } => "cannot_listen",
+        }
+    }
+
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            Error::CannotConnect { .. } | Error::CannotListen { .. } => ErrorCategory::Network,
+            Error::Protocol { .. } => ErrorCategory::Protocol,
+            Error::PathNotFound(_)
+            | Error::Serde(_)
+            | Error::CannotCreateStorage { .. }
+            | Error::CannotWrite { .. } => ErrorCategory::Storage,
+            Error::Url(_)
+            | Error::ApplicationAlreadyConnected(_)
+            | Error::ApplicationNotFound(_)
+            | Error::InvalidPath(_)
+            | Error::InvalidRecording(_)
+            | Error::InvalidQuery(_)
+            | Error::InvalidSettings(_)
+            | Error::InvalidMetadata(_)
+            | Error::InvalidEndpoint { .. }
+            | Error::PreConnectNotAllowed => ErrorCategory::Validation,
+            Error::CannotLaunch { .. } | Error::PreConnectFailed { .. } => ErrorCategory::Process,
+            Error::Anyhow(_) | Error::Collector(_) => ErrorCategory::Internal,
+            Error::Forwarded(report) => report.category,
+        }
+    }
+
+    /// Values the error is about, `null` if none
+    pub fn details(&self) -> Value {
+        match self {
+            Error::ApplicationAlreadyConnected(app_id) | Error::ApplicationNotFound(app_id) => {
+                json!({ "app_id": app_id })
+            }
+            Error::PathNotFound(path) | Error::InvalidPath(path) => json!({ "path": path }),
+            Error::CannotCreateStorage { path, .. } | Error::CannotWrite { path, .. } => {
+                json!({ "path": path })
+            }
+            Error::InvalidEndpoint { url, error } | Error::CannotConnect { url, error } => {
+                json!({ "url": url, "error": error })
+            }
+            Error::Protocol { code, message } => json!({ "grpc_code": code, "message": message }),
+            Error::CannotLaunch { program, error } => {
+                json!({ "program": program, "error": error.to_string() })
+            }
+            Error::PreConnectFailed {
+                program,
+                reason,
+                stderr,
+            } => json!({ "program": program, "reason": reason, "stderr": stderr }),
+            Error::CannotListen { address, .. } => json!({ "address": address }),
+            Error::Forwarded(report) => report.details.clone(),
+            _ => Value::Null,
+        }
+    }
+
+    pub fn report(&self) -> ErrorReport {
+        ErrorReport {
+            code: self.code().to_owned(),
+            category: self.category(),
+            message: self.to_string(),
+            details: self.details(),
+        }
+    }
+}
+
+impl From<tonic::Status> for Error {
+    fn from(status: tonic::Status) -> Self {
+        Error::Protocol {
+            code: format!("{:?}", status.code()),
+            message: status.message().to_owned(),
+        }
+    }
+}
+
+impl Serialize for Error {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::ser::Serializer,
     {
-        serializer.serialize_str(self.to_string().as_ref())
+        self.report().serialize(serializer)
     }
 }
@@ -0,0 +1,74 @@
+//! Chrome Trace Event format, every task is displayed as a thread with
+//! its observed polls. Only the last poll between two updates of the
+//! console is observed, the gaps between the polls are not idle time
+
+use super::Snapshot;
+use serde_json::{json, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const PID: u64 = 1;
+
+/// Microseconds since UNIX epoch, as used by the trace timestamps
+fn to_micros(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_micros())
+        .unwrap_or_default()
+}
+
+pub fn to_trace(snapshot: &Snapshot) -> Value {
+    let mut events = vec![json!({
+        "name": "process_name",
+        "ph": "M",
+        "pid": PID,
+        "args": { "name": snapshot.application.title() },
+    })];
+
+    for task in &snapshot.tasks {
+        let name = task
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("task {}", task.id));
+        events.push(json!({
+            "name": "thread_name",
+            "ph": "M",
+            "pid": PID,
+            "tid": task.id,
+            "args": { "name": name },
+        }));
+
+        if let Some(created_at) = task.stats.created_at {
+            events.push(json!({
+                "name": "spawn",
+                "cat": "task",
+                "ph": "i",
+                "s": "t",
+                "ts": to_micros(created_at),
+                "pid": PID,
+                "tid": task.id,
+                "args": { "location": task.location },
+            }));
+        }
+
+        let spans = snapshot
+            .poll_spans
+            .get(&task.id)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for span in spans {
+            events.push(json!({
+                "name": "poll",
+                "cat": "task",
+                "ph": "X",
+                "ts": to_micros(span.started),
+                "dur": to_micros(span.ended).saturating_sub(to_micros(span.started)),
+                "pid": PID,
+                "tid": task.id,
+            }));
+        }
+    }
+
+    json!({
+        "traceEvents": events,
+        "displayTimeUnit": "ms",
+    })
+}
@@ -0,0 +1,101 @@
+use crate::domain::{Resource, Task};
+use serde::Serialize;
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use uuid::Uuid;
+
+#[derive(Serialize)]
+struct TaskRow<'a> {
+    app_id: Uuid,
+    id: u64,
+    tid: Option<u64>,
+    name: Option<&'a str>,
+    kind: Option<&'a str>,
+    location: Option<&'a str>,
+    state: &'static str,
+    /// Milliseconds since UNIX epoch
+    created_at: Option<u128>,
+    polls: u64,
+    wakes: u64,
+    self_wakes: u64,
+    waker_clones: u64,
+    waker_drops: u64,
+    busy_ns: u128,
+    scheduled_ns: u128,
+}
+
+#[derive(Serialize)]
+struct ResourceRow<'a> {
+    app_id: Uuid,
+    id: u64,
+    kind: &'a str,
+    concrete_type: &'a str,
+    location: Option<&'a str>,
+    parent_id: Option<u64>,
+    is_internal: bool,
+    /// Attributes formatted as `name=value[unit]`, separated by `;`
+    attributes: String,
+}
+
+fn to_millis(time: Option<SystemTime>) -> Option<u128> {
+    time.and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_millis())
+}
+
+fn write_rows<T: Serialize>(rows: impl Iterator<Item = T>) -> Result<Vec<u8>, ::csv::Error> {
+    let mut writer = ::csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        writer.serialize(row)?;
+    }
+
+    writer.into_inner().map_err(|err| err.into_error().into())
+}
+
+pub fn tasks_to_csv(tasks: &[Arc<Task>]) -> Result<Vec<u8>, ::csv::Error> {
+    write_rows(tasks.iter().map(|task| TaskRow {
+        app_id: task.app_id,
+        id: task.id,
+        tid: task.tid,
+        name: task.name.as_deref(),
+        kind: task.kind.as_deref(),
+        location: task.location.as_deref(),
+        state: task.state().as_str(),
+        created_at: to_millis(task.stats.created_at),
+        polls: task.stats.polls,
+        wakes: task.stats.wakes,
+        self_wakes: task.stats.self_wakes,
+        waker_clones: task.stats.waker_clones,
+        waker_drops: task.stats.waker_drops,
+        busy_ns: task.stats.busy_time.as_nanos(),
+        scheduled_ns: task.stats.scheduled_time.as_nanos(),
+    }))
+}
+
+pub fn resources_to_csv(resources: &[Arc<Resource>]) -> Result<Vec<u8>, ::csv::Error> {
+    write_rows(resources.iter().map(|resource| {
+        ResourceRow {
+            app_id: resource.app_id,
+            id: resource.id,
+            kind: &resource.kind,
+            concrete_type: &resource.concrete_type,
+            location: resource.location.as_deref(),
+            parent_id: resource.parent_id,
+            is_internal: resource.is_internal,
+            attributes: resource
+                .attributes
+                .iter()
+                .map(|attribute| {
+                    format!(
+                        "{}={}{}",
+                        attribute.name,
+                        attribute.value,
+                        attribute.unit.as_deref().unwrap_or_default()
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(";"),
+        }
+    }))
+}
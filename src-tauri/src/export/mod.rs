@@ -0,0 +1,66 @@
+//! Serialization of an application's snapshot in formats readable by other tools
+
+mod chrome_trace;
+mod csv;
+
+use crate::domain::{application::Application, PollSpan, Resource, Task};
+use crate::error::Error as TraceError;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub enum ExportFormat {
+    /// Application, tasks and resources
+    Json,
+    TasksCsv,
+    ResourcesCsv,
+    /// Polls of the tasks in the Chrome Trace Event format (eg: for Perfetto)
+    ChromeTrace,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Json | ExportFormat::ChromeTrace => "json",
+            ExportFormat::TasksCsv | ExportFormat::ResourcesCsv => "csv",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "JSON",
+            ExportFormat::TasksCsv | ExportFormat::ResourcesCsv => "CSV",
+            ExportFormat::ChromeTrace => "Chrome Trace",
+        }
+    }
+}
+
+/// Current data of an application
+#[derive(Serialize)]
+pub struct Snapshot {
+    pub application: Arc<Application>,
+    pub tasks: Vec<Arc<Task>>,
+    pub resources: Vec<Arc<Resource>>,
+    #[serde(skip)]
+    pub poll_spans: HashMap<u64, Vec<PollSpan>>,
+}
+
+/// Serializes the snapshot in the requested format, to be written at `path`
+pub fn export(
+    snapshot: &Snapshot,
+    format: ExportFormat,
+    path: &Path,
+) -> Result<Vec<u8>, TraceError> {
+    let cannot_write = |err: ::csv::Error| TraceError::CannotWrite {
+        error: err.into(),
+        path: path.to_string_lossy().to_string(),
+    };
+    match format {
+        ExportFormat::Json => Ok(serde_json::to_vec_pretty(snapshot)?),
+        ExportFormat::TasksCsv => csv::tasks_to_csv(&snapshot.tasks).map_err(cannot_write),
+        ExportFormat::ResourcesCsv => {
+            csv::resources_to_csv(&snapshot.resources).map_err(cannot_write)
+        }
+        ExportFormat::ChromeTrace => Ok(serde_json::to_vec(&chrome_trace::to_trace(snapshot))?),
+    }
+}
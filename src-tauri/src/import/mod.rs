@@ -0,0 +1,5 @@
+//! Import of data recorded outside of a live connection
+
+mod recording;
+
+pub use recording::read_recording;
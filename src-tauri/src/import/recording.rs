@@ -0,0 +1,288 @@
+//! Reader of the files written by console-subscriber when its recording
+//! path is set (eg: `TOKIO_CONSOLE_RECORD_PATH`)
+//!
+//! A recording is a list of JSON lines, a header followed by the span events
+//! of the tasks. The events are replayed into the `Update`s a live connection
+//! would have received, one per second of recording. Only tasks are recorded,
+//! the recordings do not contain any resource or async op.
+
+use crate::error::Error as TraceError;
+use crate::mappers::read_file;
+use console_api::{
+    field::{Name, Value},
+    instrument::Update,
+    tasks::{task::Kind, Stats, Task, TaskUpdate},
+    Field, Id, PollStats,
+};
+use prost_types::Timestamp;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+/// Recording format understood by the importer
+const DATA_FORMAT_VERSION: u8 = 1;
+
+/// Time covered by every replayed update
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Deserialize)]
+struct Header {
+    v: u8,
+}
+
+#[derive(Deserialize)]
+struct RecordedField {
+    name: String,
+    value: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+enum WakeOp {
+    Wake { self_wake: bool },
+    WakeByRef { self_wake: bool },
+    Clone,
+    Drop,
+}
+
+#[derive(Deserialize)]
+enum RecordedEvent {
+    Spawn {
+        id: u64,
+        at: SystemTime,
+        fields: Vec<RecordedField>,
+    },
+    Enter {
+        id: u64,
+        at: SystemTime,
+    },
+    Exit {
+        id: u64,
+        at: SystemTime,
+    },
+    Close {
+        id: u64,
+        at: SystemTime,
+    },
+    Waker {
+        id: u64,
+        op: WakeOp,
+        at: SystemTime,
+    },
+}
+
+/// Stats of a recorded task, rebuilt from its events
+#[derive(Default)]
+struct TaskRecord {
+    stats: Stats,
+    busy_time: Duration,
+    scheduled_time: Duration,
+    poll_started: Option<SystemTime>,
+    woken_at: Option<SystemTime>,
+}
+
+impl TaskRecord {
+    fn snapshot(&self) -> Stats {
+        let mut stats = self.stats;
+        let poll_stats = stats.poll_stats.get_or_insert_with(PollStats::default);
+        poll_stats.busy_time = self.busy_time.try_into().ok();
+        stats.scheduled_time = self.scheduled_time.try_into().ok();
+        stats
+    }
+}
+
+fn map_field(field: RecordedField) -> Field {
+    let value = match field.value {
+        serde_json::Value::Bool(value) => Value::BoolVal(value),
+        serde_json::Value::Number(number) => match number.as_u64() {
+            Some(value) => Value::U64Val(value),
+            None => Value::I64Val(number.as_i64().unwrap_or_default()),
+        },
+        serde_json::Value::String(value) => Value::StrVal(value),
+        value => Value::DebugVal(value.to_string()),
+    };
+
+    Field {
+        name: Some(Name::StrName(field.name)),
+        value: Some(value),
+        metadata_id: None,
+    }
+}
+
+fn map_task(id: u64, fields: Vec<RecordedField>) -> Task {
+    let fields: Vec<Field> = fields.into_iter().map(map_field).collect();
+    let blocking = fields.iter().any(|field| {
+        field.name == Some(Name::StrName("kind".to_owned()))
+            && field.value == Some(Value::StrVal("blocking".to_owned()))
+    });
+
+    Task {
+        id: Some(Id { id }),
+        kind: if blocking {
+            Kind::Blocking
+        } else {
+            Kind::Spawn
+        } as i32,
+        fields,
+        ..Default::default()
+    }
+}
+
+/// Replays the events of a recording into updates
+#[derive(Default)]
+struct Replay {
+    updates: Vec<Update>,
+    tasks: HashMap<u64, TaskRecord>,
+    new_tasks: Vec<Task>,
+    changed: BTreeSet<u64>,
+    update_end: Option<SystemTime>,
+}
+
+impl Replay {
+    fn handle(&mut self, event: RecordedEvent) {
+        let at = match &event {
+            RecordedEvent::Spawn { at, .. }
+            | RecordedEvent::Enter { at, .. }
+            | RecordedEvent::Exit { at, .. }
+            | RecordedEvent::Close { at, .. }
+            | RecordedEvent::Waker { at, .. } => *at,
+        };
+        match self.update_end {
+            Some(update_end) if at >= update_end => {
+                self.flush(update_end);
+                // Nothing happened during a gap, the next update ends after the event
+                let next_end = update_end + UPDATE_INTERVAL;
+                self.update_end = Some(if next_end > at {
+                    next_end
+                } else {
+                    at + UPDATE_INTERVAL
+                });
+            }
+            None => self.update_end = Some(at + UPDATE_INTERVAL),
+            _ => {}
+        }
+
+        match event {
+            RecordedEvent::Spawn { id, at, fields } => {
+                let mut record = TaskRecord::default();
+                record.stats.created_at = Some(Timestamp::from(at));
+                self.tasks.insert(id, record);
+                self.new_tasks.push(map_task(id, fields));
+                self.changed.insert(id);
+            }
+            // Spans which are not tasks (eg: async ops) are skipped
+            RecordedEvent::Enter { id, at } => {
+                if let Some(record) = self.tasks.get_mut(&id) {
+                    let poll_stats = record.stats.poll_stats.get_or_insert_with(Default::default);
+                    poll_stats.polls += 1;
+                    poll_stats.first_poll.get_or_insert(Timestamp::from(at));
+                    poll_stats.last_poll_started = Some(Timestamp::from(at));
+                    if let Some(woken_at) = record.woken_at.take() {
+                        record.scheduled_time += at.duration_since(woken_at).unwrap_or_default();
+                    }
+                    record.poll_started = Some(at);
+                    self.changed.insert(id);
+                }
+            }
+            RecordedEvent::Exit { id, at } => {
+                if let Some(record) = self.tasks.get_mut(&id) {
+                    if let Some(started) = record.poll_started.take() {
+                        record.busy_time += at.duration_since(started).unwrap_or_default();
+                    }
+                    let poll_stats = record.stats.poll_stats.get_or_insert_with(Default::default);
+                    poll_stats.last_poll_ended = Some(Timestamp::from(at));
+                    self.changed.insert(id);
+                }
+            }
+            RecordedEvent::Close { id, at } => {
+                if let Some(record) = self.tasks.get_mut(&id) {
+                    record.stats.dropped_at = Some(Timestamp::from(at));
+                    self.changed.insert(id);
+                }
+            }
+            RecordedEvent::Waker { id, op, at } => {
+                if let Some(record) = self.tasks.get_mut(&id) {
+                    let stats = &mut record.stats;
+                    match op {
+                        WakeOp::Wake { self_wake } | WakeOp::WakeByRef { self_wake } => {
+                            stats.wakes += 1;
+                            if self_wake {
+                                stats.self_wakes += 1;
+                            }
+                            // Waking by value consumes the waker
+                            if matches!(op, WakeOp::Wake { .. }) {
+                                stats.waker_drops += 1;
+                            }
+                            stats.last_wake = Some(Timestamp::from(at));
+                            record.woken_at.get_or_insert(at);
+                        }
+                        WakeOp::Clone => stats.waker_clones += 1,
+                        WakeOp::Drop => stats.waker_drops += 1,
+                    }
+                    self.changed.insert(id);
+                }
+            }
+        }
+    }
+
+    /// Builds the update holding the changes since the previous one
+    fn flush(&mut self, now: SystemTime) {
+        let mut stats_update = HashMap::new();
+        for id in std::mem::take(&mut self.changed) {
+            if let Some(record) = self.tasks.get(&id) {
+                stats_update.insert(id, record.snapshot());
+                // Span ids are reused once closed
+                if record.stats.dropped_at.is_some() {
+                    self.tasks.remove(&id);
+                }
+            }
+        }
+
+        self.updates.push(Update {
+            now: Some(Timestamp::from(now)),
+            task_update: Some(TaskUpdate {
+                new_tasks: std::mem::take(&mut self.new_tasks),
+                stats_update,
+                dropped_events: 0,
+            }),
+            ..Default::default()
+        });
+    }
+}
+
+/// Reads a recording file and returns the updates describing it
+///
+/// # Error
+///
+/// If the file cannot be read or is not a recording in a known format,
+/// an error will be returned
+pub async fn read_recording(path: &Path) -> Result<Vec<Update>, TraceError> {
+    let content = read_file(&path.to_string_lossy()).await?;
+    let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+
+    let header: Header = lines
+        .next()
+        .ok_or_else(|| TraceError::InvalidRecording("the file is empty".to_owned()))
+        .and_then(|line| Ok(serde_json::from_str(line)?))?;
+    if header.v != DATA_FORMAT_VERSION {
+        return Err(TraceError::InvalidRecording(format!(
+            "unsupported format version {}",
+            header.v
+        )));
+    }
+
+    let mut replay = Replay::default();
+    for (index, line) in lines.enumerate() {
+        let event = serde_json::from_str(line).map_err(|err| {
+            TraceError::InvalidRecording(format!("line {} is not an event ({err})", index + 2))
+        })?;
+        replay.handle(event);
+    }
+    if let Some(update_end) = replay.update_end {
+        replay.flush(update_end);
+    }
+
+    Ok(replay.updates)
+}
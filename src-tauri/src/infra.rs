@@ -1,2 +1,3 @@
 pub mod guard;
+pub mod snapshot;
 pub mod storage;
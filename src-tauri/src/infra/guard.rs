@@ -1,24 +1,24 @@
-use crate::error::Error as TraceError;
-use log::{error, info};
+use super::snapshot::Snapshot;
 use serde::Serialize;
 use std::{
     ops::{Deref, DerefMut},
     sync::Arc,
 };
-use tokio::sync::RwLockWriteGuard;
+use tokio::sync::MutexGuard;
 
 pub trait DataBaseWrite<D: Serialize + Clone> {
     #[allow(unused)]
     fn writeable(&mut self) -> &mut D;
 }
 
-pub struct WriteableDataBaseGuard<'a, D: Serialize> {
-    pub(crate) folder: &'a str,
-    pub(crate) title: &'a str,
-    pub(crate) elements: RwLockWriteGuard<'a, D>,
+pub struct WriteableDataBaseGuard<'a, D: Serialize + Clone + Default> {
+    /// Copy of the elements, published to the readers once dropped
+    pub(crate) elements: D,
+    pub(crate) snapshot: &'a Snapshot<D>,
+    pub(crate) _writer: MutexGuard<'a, ()>,
 }
 
-impl<D: Serialize> Deref for WriteableDataBaseGuard<'_, D> {
+impl<D: Serialize + Clone + Default> Deref for WriteableDataBaseGuard<'_, D> {
     type Target = D;
 
     fn deref(&self) -> &Self::Target {
@@ -26,7 +26,7 @@ impl<D: Serialize> Deref for WriteableDataBaseGuard<'_, D> {
     }
 }
 
-impl<D: Serialize> DerefMut for WriteableDataBaseGuard<'_, D> {
+impl<D: Serialize + Clone + Default> DerefMut for WriteableDataBaseGuard<'_, D> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.elements
     }
@@ -38,22 +38,8 @@ impl<D: Serialize + Clone> DataBaseWrite<D> for Arc<D> {
     }
 }
 
-impl<D: Serialize> Drop for WriteableDataBaseGuard<'_, D> {
+impl<D: Serialize + Clone + Default> Drop for WriteableDataBaseGuard<'_, D> {
     fn drop(&mut self) {
-        let filename = format!("{}/{}.json", self.folder, self.title);
-        info!("Storing {} to {filename}", self.title);
-        serde_json::to_string_pretty(&*self.elements)
-            .map_err(|error| {
-                error!("Failed to serialize {filename} ({error})");
-                TraceError::Serde(error)
-            })
-            .map(|json| {
-                std::fs::write(&filename, json).map_err(|err| {
-                    error!("Failed to write {filename} ({err:?})");
-                    TraceError::Anyhow(err.into())
-                })
-            })
-            .ok();
-        info!("Dropped {}", self.title);
+        self.snapshot.publish(std::mem::take(&mut self.elements));
     }
 }
@@ -0,0 +1,193 @@
+use super::guard::WriteableDataBaseGuard;
+use crate::error::Error as TraceError;
+use arc_swap::ArcSwap;
+use log::{error, info};
+use serde::Serialize;
+use std::{
+    sync::{Arc, Weak},
+    time::Duration,
+};
+use tokio::{
+    sync::{Mutex, Notify},
+    task,
+    time::sleep,
+};
+
+/// File a snapshot is written to
+#[derive(Clone)]
+struct SnapshotFile {
+    path: String,
+    /// Only readable by the user (eg: secrets)
+    private: bool,
+}
+
+/// Data read without locking: readers get the latest published snapshot,
+/// while writers change a copy which is published once they are done
+///
+/// Used with persistent collections, so that copying a snapshot is cheap
+/// whatever its size
+pub struct Snapshot<D> {
+    current: Arc<ArcSwap<D>>,
+    /// Held by the writer, so that the changes are applied one after the other
+    writer: Mutex<()>,
+    /// Kept in memory only if there is no file
+    file: Option<SnapshotFile>,
+    /// Set when the file is written in the background, signals the changes
+    pending: Option<Arc<Notify>>,
+}
+
+impl<D: Serialize + Clone + Default> Default for Snapshot<D> {
+    fn default() -> Self {
+        Self::in_memory(D::default())
+    }
+}
+
+impl<D: Serialize + Clone + Default> Snapshot<D> {
+    fn with_file(elements: D, file: Option<SnapshotFile>, pending: Option<Arc<Notify>>) -> Self {
+        Self {
+            current: Arc::new(ArcSwap::from_pointee(elements)),
+            writer: Mutex::new(()),
+            file,
+            pending,
+        }
+    }
+
+    /// Creates a snapshot which is never written to the disk
+    pub fn in_memory(elements: D) -> Self {
+        Self::with_file(elements, None, None)
+    }
+
+    /// Creates a snapshot written to `{folder}/{title}.json` after every change
+    pub fn stored(elements: D, folder: &str, title: &str, private: bool) -> Self {
+        let file = SnapshotFile {
+            path: format!("{folder}/{title}.json"),
+            private,
+        };
+        Self::with_file(elements, Some(file), None)
+    }
+
+    /// Returns the latest published snapshot, never waits for the writers
+    pub fn read(&self) -> Arc<D> {
+        self.current.load_full()
+    }
+
+    /// Waits for the previous writer and returns a copy of the latest snapshot,
+    /// published and written to the disk (if there is a file) once dropped
+    pub async fn write(&self) -> WriteableDataBaseGuard<'_, D> {
+        let writer = self.writer.lock().await;
+        WriteableDataBaseGuard {
+            elements: D::clone(&self.current.load()),
+            snapshot: self,
+            _writer: writer,
+        }
+    }
+
+    /// Makes the changes of a writer visible to the readers and stores them
+    pub(super) fn publish(&self, elements: D) {
+        let elements = Arc::new(elements);
+        self.current.store(elements.clone());
+
+        if let Some(pending) = &self.pending {
+            pending.notify_one();
+        } else if let Some(file) = &self.file {
+            info!("Storing {}", file.path);
+            let json = serde_json::to_string_pretty(&*elements).map_err(TraceError::from);
+            if let Err(err) = json.and_then(|json| file.write(json.as_bytes())) {
+                error!("Failed to store {} ({err:?})", file.path);
+            }
+        }
+    }
+}
+
+impl<D: Serialize + Clone + Default + Send + Sync + 'static> Snapshot<D> {
+    /// Creates a snapshot written to `{folder}/{title}.json` in the background,
+    /// at most once every `delay`
+    ///
+    /// Used for the large files, which would otherwise be rewritten on every
+    /// update. The changes of the last `delay` are lost if the process stops.
+    pub fn stored_in_background(elements: D, folder: &str, title: &str, delay: Duration) -> Self {
+        let file = SnapshotFile {
+            path: format!("{folder}/{title}.json"),
+            private: false,
+        };
+        let pending = Arc::new(Notify::new());
+        let snapshot = Self::with_file(elements, Some(file.clone()), Some(pending.clone()));
+
+        task::spawn(write_in_background(
+            Arc::downgrade(&snapshot.current),
+            pending,
+            file,
+            delay,
+        ));
+
+        snapshot
+    }
+}
+
+impl<D> Drop for Snapshot<D> {
+    fn drop(&mut self) {
+        // Stops the background writer
+        if let Some(pending) = &self.pending {
+            pending.notify_one();
+        }
+    }
+}
+
+/// Writes the latest snapshot once changed, until the snapshot is dropped
+async fn write_in_background<D: Serialize + Send + Sync + 'static>(
+    current: Weak<ArcSwap<D>>,
+    pending: Arc<Notify>,
+    file: SnapshotFile,
+    delay: Duration,
+) {
+    loop {
+        pending.notified().await;
+        // The changes made meanwhile are written together
+        sleep(delay).await;
+        let Some(elements) = current.upgrade().map(|current| current.load_full()) else {
+            break;
+        };
+
+        let writer = file.clone();
+        let written = task::spawn_blocking(move || {
+            // Compact, as these files are large
+            let json = serde_json::to_vec(&*elements)?;
+            writer.write(&json)
+        })
+        .await;
+        match written {
+            Ok(Ok(())) => info!("Stored {}", file.path),
+            Ok(Err(err)) => error!("Failed to store {} ({err:?})", file.path),
+            Err(err) => error!("Failed to store {} ({err:?})", file.path),
+        }
+    }
+}
+
+impl SnapshotFile {
+    /// Writes the file, private files are only readable and writable by the user
+    fn write(&self, contents: &[u8]) -> Result<(), TraceError> {
+        write_file(&self.path, contents, self.private).map_err(|err| TraceError::CannotWrite {
+            error: err.into(),
+            path: self.path.clone(),
+        })
+    }
+}
+
+fn write_file(filename: &str, contents: &[u8], private: bool) -> std::io::Result<()> {
+    if !private {
+        return std::fs::write(filename, contents);
+    }
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // The mode is only applied to new files
+        if std::path::Path::new(filename).exists() {
+            std::fs::set_permissions(filename, std::fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    std::io::Write::write_all(&mut options.open(filename)?, contents)
+}
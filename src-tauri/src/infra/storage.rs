@@ -1,18 +1,29 @@
 use super::guard::WriteableDataBaseGuard;
-use crate::domain::{application::Application, Task};
+use crate::domain::{application::Application, secrets::Secrets, settings::Settings, Task};
 use async_trait::async_trait;
-use std::{collections::HashMap, sync::Arc};
+use std::sync::Arc;
 use uuid::Uuid;
 
+/// Persistent maps, their copies share their elements so they are cheap to make
+pub(crate) type Applications = im::HashMap<Uuid, Arc<Application>>;
+pub type Tasks = im::HashMap<String, Arc<Task>>;
+
+/// Reads return a snapshot of the data, without waiting for the writers
 #[async_trait]
 pub(crate) trait Storage: Send + Sync {
-    async fn applications_read(&self) -> HashMap<Uuid, Arc<Application>>;
+    async fn applications_read(&self) -> Applications;
+
+    async fn applications_write(&self) -> WriteableDataBaseGuard<'_, Applications>;
+
+    async fn tasks_read(&self) -> Tasks;
+
+    async fn tasks_write(&self) -> WriteableDataBaseGuard<'_, Tasks>;
+
+    async fn settings_read(&self) -> Settings;
 
-    async fn applications_write(
-        &self,
-    ) -> WriteableDataBaseGuard<'_, HashMap<Uuid, Arc<Application>>>;
+    async fn settings_write(&self) -> WriteableDataBaseGuard<'_, Settings>;
 
-    async fn tasks_read(&self) -> HashMap<String, Arc<Task>>;
+    async fn secrets_read(&self) -> Secrets;
 
-    async fn tasks_write(&self) -> WriteableDataBaseGuard<'_, HashMap<String, Arc<Task>>>;
+    async fn secrets_write(&self) -> WriteableDataBaseGuard<'_, Secrets>;
 }
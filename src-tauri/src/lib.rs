@@ -1,25 +1,85 @@
+mod analyzers;
+mod collector;
 mod commands;
+mod discovery;
 mod domain;
+mod endpoints;
 mod error;
+mod export;
+mod import;
 mod infra;
 mod mappers;
+mod proxy;
+mod query;
 mod state_manager;
 mod ui_manager;
 
+/// Internals measured by the benchmarks, not meant to be used otherwise
+#[doc(hidden)]
+pub mod bench {
+    pub use crate::domain::Task;
+    pub use crate::infra::{snapshot::Snapshot, storage::Tasks};
+}
+
+use collector::client::CollectorClient;
+use log::error;
 use state_manager::StateManager;
 use std::{sync::Arc, time::Duration};
 use tauri::{async_runtime, Manager};
 use tokio::{task, time::sleep};
 
-pub async fn run() {
-    // Load context
+/// Runs the collector, which keeps the connections to the applications
+/// and stores their data without any window, until killed
+pub async fn run_collector() {
     let (state_manager, updates_receiver) = StateManager::new()
         .await
-        // TODO: should we panic here or disable the persistency?
-        .unwrap_or_else(|err| panic!("Cannot start application due to {err:?}"));
+        .unwrap_or_else(|err| panic!("Cannot start the collector due to {err:?}"));
+    let state_manager = Arc::new(state_manager);
+    // The windows receive the state of every application once attached
+    state_manager.proxy_hub.mirror_all();
+
+    state_manager.start_endpoints().await;
+
+    let runner = state_manager.clone();
+    task::spawn(async move {
+        runner.run(updates_receiver).await;
+    });
+
+    if let Err(err) = collector::server::serve(state_manager).await {
+        error!("The collector stopped due to {err:?}");
+    }
+}
+
+pub async fn run() {
+    // Load context, from the collector if one is running
+    let (state_manager, updates_receiver, collector_frames) = match CollectorClient::connect().await
+    {
+        Some((collector, frames)) => {
+            let (state_manager, updates_receiver) = StateManager::new_attached(collector);
+            (state_manager, updates_receiver, Some(frames))
+        }
+        None => {
+            let (state_manager, updates_receiver) = StateManager::new()
+                .await
+                // TODO: should we panic here or disable the persistency?
+                .unwrap_or_else(|err| panic!("Cannot start application due to {err:?}"));
+            (state_manager, updates_receiver, None)
+        }
+    };
 
     let shared_state = Arc::new(state_manager);
 
+    match collector_frames {
+        Some(frames) => {
+            task::spawn(collector::client::receive(shared_state.clone(), frames));
+        }
+        // Start the endpoints enabled by the user, the application is still
+        // usable without them
+        None => {
+            shared_state.start_endpoints().await;
+        }
+    }
+
     // Start job
     let state_manager = shared_state.clone();
     task::spawn(async move {
@@ -51,6 +111,11 @@ pub async fn run() {
                     sleep(Duration::from_secs(1)).await;
                     ui_state_manager.emit_update_applications(&app_handle).await;
                     ui_state_manager.emit_update_tasks(&app_handle).await;
+                    ui_state_manager.emit_deadlock_warnings(&app_handle).await;
+                    ui_state_manager.emit_leak_warnings(&app_handle).await;
+                    ui_state_manager.emit_blocking_warnings(&app_handle).await;
+                    ui_state_manager.emit_data_loss_warnings(&app_handle).await;
+                    ui_state_manager.emit_task_groups(&app_handle).await;
                 }
             });
 
@@ -60,7 +125,31 @@ pub async fn run() {
         .invoke_handler(tauri::generate_handler![
             commands::applications::applications_add,
             commands::applications::delete_application,
+            commands::applications::update_application_metadata,
             commands::applications::disable_app,
+            commands::applications::import_recording,
+            commands::applications::set_application_proxy,
+            commands::applications::launch_application,
+            commands::applications::application_output,
+            commands::applications::connection_errors,
+            commands::applications::data_loss,
+            commands::applications::purge_application_history,
+            commands::applications::ingestion_stats,
+            commands::applications::discover_applications,
+            commands::metrics::metrics_history,
+            commands::tasks::task_histograms,
+            commands::tasks::location_histograms,
+            commands::tasks::tasks_query,
+            commands::tasks::task_groups,
+            commands::tasks::watch_task_groups,
+            commands::tasks::task_leaks,
+            commands::tasks::blocking_report,
+            commands::metrics::scheduling_report,
+            commands::metrics::windowed_rates,
+            commands::metrics::top_tasks,
+            commands::export::export_application,
+            commands::settings::get_settings,
+            commands::settings::update_settings,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
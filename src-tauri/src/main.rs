@@ -5,6 +5,12 @@
 async fn main() {
     env_logger::init();
 
+    // Without window, see the collector module
+    if std::env::args().any(|arg| arg == "--collector") {
+        println!("Starting the collector");
+        return tokio_display_lib::run_collector().await;
+    }
+
     println!("Starting");
     tokio_display_lib::run().await
 }
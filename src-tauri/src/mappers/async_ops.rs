@@ -0,0 +1,28 @@
+use super::map_timestamp;
+use crate::domain::AsyncOp;
+use console_api::async_ops;
+use uuid::Uuid;
+
+pub fn map_to_domain_async_op(app_id: Uuid, async_op: &async_ops::AsyncOp) -> Option<AsyncOp> {
+    let id = async_op.id.map(|value| value.id)?;
+    Some(AsyncOp {
+        app_id,
+        id,
+        source: async_op.source.clone(),
+        resource_id: async_op.resource_id.map(|value| value.id),
+        parent_id: async_op.parent_async_op_id.map(|value| value.id),
+        task_id: None,
+        created_at: None,
+        polls: 0,
+        pending_since: None,
+    })
+}
+
+/// Updates the async op with its latest stats
+pub fn update_domain_async_op(async_op: &mut AsyncOp, stats: &async_ops::Stats) {
+    async_op.task_id = stats.task_id.map(|value| value.id).or(async_op.task_id);
+    async_op.created_at = map_timestamp(stats.created_at.as_ref());
+    if let Some(poll_stats) = &stats.poll_stats {
+        async_op.polls = poll_stats.polls;
+    }
+}
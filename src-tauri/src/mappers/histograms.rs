@@ -0,0 +1,123 @@
+use crate::domain::{DurationHistogram, TaskHistograms};
+use console_api::tasks::{self, task_details::PollTimesHistogram};
+use hdrhistogram::{serialization::Deserializer, Histogram};
+use log::warn;
+
+/// Decodes a HdrHistogram serialized in the V2 format
+fn decode_histogram(bytes: &[u8]) -> Option<Histogram<u64>> {
+    Deserializer::new()
+        .deserialize(&mut &bytes[..])
+        .map_err(|error| warn!("Failed to decode histogram ({error:?})"))
+        .ok()
+}
+
+fn map_to_domain_histogram(histogram: &tasks::DurationHistogram) -> Option<DurationHistogram> {
+    let raw = decode_histogram(&histogram.raw_histogram)?;
+    Some(DurationHistogram::new(
+        raw,
+        histogram.high_outliers,
+        histogram.highest_outlier,
+    ))
+}
+
+pub fn map_to_domain_histograms(details: &tasks::TaskDetails) -> TaskHistograms {
+    let poll_times = match &details.poll_times_histogram {
+        Some(PollTimesHistogram::Histogram(histogram)) => map_to_domain_histogram(histogram),
+        Some(PollTimesHistogram::LegacyHistogram(bytes)) => {
+            decode_histogram(bytes).map(|raw| DurationHistogram::new(raw, 0, None))
+        }
+        None => None,
+    };
+    let scheduled_times = details
+        .scheduled_times_histogram
+        .as_ref()
+        .and_then(map_to_domain_histogram);
+
+    TaskHistograms {
+        poll_times,
+        scheduled_times,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Poll times of 1µs to 100µs, serialized as sent by the applications
+    const POLL_TIMES: &[u8] = include_bytes!("fixtures/poll_times.hdr");
+
+    fn details(poll_times: PollTimesHistogram) -> tasks::TaskDetails {
+        tasks::TaskDetails {
+            task_id: None,
+            now: None,
+            scheduled_times_histogram: None,
+            poll_times_histogram: Some(poll_times),
+        }
+    }
+
+    fn poll_times(high_outliers: u64, highest_outlier: Option<u64>) -> DurationHistogram {
+        let details = details(PollTimesHistogram::Histogram(tasks::DurationHistogram {
+            raw_histogram: POLL_TIMES.to_vec(),
+            max_value: 1_000_000_000,
+            high_outliers,
+            highest_outlier,
+        }));
+        map_to_domain_histograms(&details).poll_times.unwrap()
+    }
+
+    #[test]
+    fn extracts_the_percentiles() {
+        let histogram = poll_times(2, Some(5_000_000));
+        assert_eq!(histogram.count, 100);
+        // Values are rounded to the precision of the histogram
+        assert_eq!(histogram.min, Duration::from_nanos(1_000));
+        assert_eq!(histogram.p50, Duration::from_nanos(50_175));
+        assert_eq!(histogram.p90, Duration::from_nanos(90_111));
+        assert_eq!(histogram.p99, Duration::from_nanos(99_327));
+        // The outliers are longer than any recorded value
+        assert_eq!(histogram.max, Duration::from_millis(5));
+        assert_eq!(histogram.high_outliers, 2);
+        assert_eq!(
+            histogram
+                .buckets
+                .iter()
+                .map(|bucket| bucket.count)
+                .sum::<u64>(),
+            100
+        );
+        assert_eq!(histogram.count_above(Duration::from_micros(50)), 52);
+    }
+
+    #[test]
+    fn decodes_the_legacy_histograms() {
+        let details = details(PollTimesHistogram::LegacyHistogram(POLL_TIMES.to_vec()));
+        let histogram = map_to_domain_histograms(&details).poll_times.unwrap();
+        assert_eq!(histogram.count, 100);
+        assert_eq!(histogram.max, Duration::from_nanos(100_351));
+        assert_eq!(histogram.high_outliers, 0);
+    }
+
+    #[test]
+    fn ignores_the_corrupted_histograms() {
+        let corrupted = &POLL_TIMES[..POLL_TIMES.len() / 2];
+        let details = details(PollTimesHistogram::LegacyHistogram(corrupted.to_vec()));
+        assert!(map_to_domain_histograms(&details).poll_times.is_none());
+        assert!(decode_histogram(b"not a histogram").is_none());
+    }
+
+    #[test]
+    fn merges_the_histograms() {
+        let histograms = [
+            poll_times(1, Some(2_000_000)),
+            poll_times(2, Some(5_000_000)),
+        ];
+        let merged = DurationHistogram::merge(&histograms).unwrap();
+        assert_eq!(merged.count, 200);
+        // Merged with a finer precision than the one of the applications
+        assert_eq!(merged.p50, Duration::from_nanos(49_951));
+        assert_eq!(merged.high_outliers, 3);
+        assert_eq!(merged.max, Duration::from_millis(5));
+        assert!(DurationHistogram::merge(&[]).is_none());
+    }
+}
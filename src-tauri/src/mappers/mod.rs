@@ -1,18 +1,23 @@
+pub(crate) mod async_ops;
+pub(crate) mod histograms;
+pub(crate) mod resources;
 pub(crate) mod tasks;
 
+use crate::domain::Attribute;
 use crate::error::Error as TraceError;
 use console_api::{
     field::{Name, Value},
-    tasks::Task as ConsoleTask,
-    Field,
+    Attribute as ConsoleAttribute, Field, Location,
 };
 use log::error;
+use prost_types::{Duration as ProtoDuration, Timestamp};
+use std::time::{Duration, SystemTime};
 use tokio::fs::read_to_string;
 
 // UTILS METHODS (could be moved in a dedicated module)
 
-fn find_field(task: &ConsoleTask, field_name: impl AsRef<str>) -> Option<&Field> {
-    task.fields.iter().find(|field| {
+fn find_field(fields: &[Field], field_name: impl AsRef<str>) -> Option<&Field> {
+    fields.iter().find(|field| {
         if let Some(Name::StrName(ref s)) = field.name {
             s == field_name.as_ref()
         } else {
@@ -21,8 +26,8 @@ fn find_field(task: &ConsoleTask, field_name: impl AsRef<str>) -> Option<&Field>
     })
 }
 
-fn read_field_value_u64(task: &ConsoleTask, field_name: impl AsRef<str>) -> Option<u64> {
-    if let Some(field) = find_field(task, field_name) {
+fn read_field_value_u64(fields: &[Field], field_name: impl AsRef<str>) -> Option<u64> {
+    if let Some(field) = find_field(fields, field_name) {
         match field.value {
             Some(Value::U64Val(value)) => Some(value),
             _ => None,
@@ -32,8 +37,8 @@ fn read_field_value_u64(task: &ConsoleTask, field_name: impl AsRef<str>) -> Opti
     }
 }
 
-fn read_field_value_string(task: &ConsoleTask, field_name: impl AsRef<str>) -> Option<&str> {
-    if let Some(field) = find_field(task, field_name) {
+fn read_field_value_string(fields: &[Field], field_name: impl AsRef<str>) -> Option<&str> {
+    if let Some(field) = find_field(fields, field_name) {
         match field.value {
             Some(Value::DebugVal(ref value)) | Some(Value::StrVal(ref value)) => Some(value),
             _ => None,
@@ -43,6 +48,41 @@ fn read_field_value_string(task: &ConsoleTask, field_name: impl AsRef<str>) -> O
     }
 }
 
+fn map_location(location: Option<&Location>) -> Option<String> {
+    location
+        .filter(|location| location.file.is_some() || location.module_path.is_some())
+        .map(|location| location.to_string())
+}
+
+/// Maps the attributes of a resource or async op, skipping the ones
+/// whose name was not sent as a string
+fn map_attributes(attributes: &[ConsoleAttribute]) -> Vec<Attribute> {
+    attributes
+        .iter()
+        .filter_map(|attribute| {
+            let field = attribute.field.as_ref()?;
+            match (&field.name, &field.value) {
+                (Some(Name::StrName(name)), Some(value)) => Some(Attribute {
+                    name: name.clone(),
+                    value: value.to_string(),
+                    unit: attribute.unit.clone(),
+                }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn map_timestamp(timestamp: Option<&Timestamp>) -> Option<SystemTime> {
+    timestamp.and_then(|timestamp| SystemTime::try_from(*timestamp).ok())
+}
+
+pub(crate) fn map_duration(duration: Option<&ProtoDuration>) -> Duration {
+    duration
+        .and_then(|duration| Duration::try_from(*duration).ok())
+        .unwrap_or_default()
+}
+
 pub async fn read_file(filename: &str) -> Result<String, TraceError> {
     read_to_string(filename).await.map_err(|err| {
         error!("Failed to load {filename} ({err:?})");
@@ -0,0 +1,32 @@
+use super::{map_attributes, map_location};
+use crate::domain::Resource;
+use console_api::resources;
+use console_api::resources::resource::kind::{Kind, Known};
+use uuid::Uuid;
+
+pub fn map_to_domain_resource(app_id: Uuid, resource: &resources::Resource) -> Option<Resource> {
+    let id = resource.id.map(|value| value.id)?;
+    let kind = match resource.kind.as_ref().and_then(|kind| kind.kind.as_ref()) {
+        Some(Kind::Known(known)) => Known::try_from(*known)
+            .map(|known| known.as_str_name().to_owned())
+            .unwrap_or_default(),
+        Some(Kind::Other(other)) => other.clone(),
+        None => String::new(),
+    };
+    Some(Resource {
+        app_id,
+        id,
+        kind,
+        concrete_type: resource.concrete_type.clone(),
+        location: map_location(resource.location.as_ref()),
+        parent_id: resource.parent_resource_id.map(|value| value.id),
+        is_internal: resource.is_internal,
+        attributes: Vec::new(),
+        holder_task_id: None,
+    })
+}
+
+/// Updates the resource with its latest stats
+pub fn update_domain_resource(resource: &mut Resource, stats: &resources::Stats) {
+    resource.attributes = map_attributes(&stats.attributes);
+}
@@ -1,21 +1,52 @@
-use super::{read_field_value_string, read_field_value_u64};
-use crate::domain::Task;
+use super::{
+    map_duration, map_location, map_timestamp, read_field_value_string, read_field_value_u64,
+};
+use crate::domain::{Task, TaskStats};
 use console_api::tasks;
 use console_api::tasks::task::Kind;
 use uuid::Uuid;
 
 pub fn map_to_domain_task(app_id: Uuid, task: &tasks::Task) -> Option<Task> {
     let id = task.id.map(|value| value.id)?;
-    let tid = read_field_value_u64(task, "task.id");
-    let name = read_field_value_string(task, "task.name").map(|s| s.to_owned());
+    let tid = read_field_value_u64(&task.fields, "task.id");
+    let name = read_field_value_string(&task.fields, "task.name").map(|s| s.to_owned());
     let kind = Kind::try_from(task.kind)
         .map(|kind| kind.as_str_name().to_owned())
         .ok();
+    let location = map_location(task.location.as_ref());
     Some(Task {
         app_id,
         id,
         tid,
         name,
         kind,
+        location,
+        stats: TaskStats::default(),
     })
 }
+
+/// Updates the task with its latest stats
+pub fn update_domain_task(task: &mut Task, stats: &tasks::Stats) {
+    let poll_stats = stats.poll_stats.as_ref();
+    task.stats = TaskStats {
+        created_at: map_timestamp(stats.created_at.as_ref()),
+        dropped_at: map_timestamp(stats.dropped_at.as_ref()),
+        wakes: stats.wakes,
+        waker_clones: stats.waker_clones,
+        waker_drops: stats.waker_drops,
+        self_wakes: stats.self_wakes,
+        last_wake: map_timestamp(stats.last_wake.as_ref()),
+        polls: poll_stats
+            .map(|poll_stats| poll_stats.polls)
+            .unwrap_or_default(),
+        first_poll: map_timestamp(poll_stats.and_then(|poll_stats| poll_stats.first_poll.as_ref())),
+        last_poll_started: map_timestamp(
+            poll_stats.and_then(|poll_stats| poll_stats.last_poll_started.as_ref()),
+        ),
+        last_poll_ended: map_timestamp(
+            poll_stats.and_then(|poll_stats| poll_stats.last_poll_ended.as_ref()),
+        ),
+        busy_time: map_duration(poll_stats.and_then(|poll_stats| poll_stats.busy_time.as_ref())),
+        scheduled_time: map_duration(stats.scheduled_time.as_ref()),
+    };
+}
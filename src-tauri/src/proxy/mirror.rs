@@ -0,0 +1,158 @@
+use console_api::{
+    async_ops::{self, AsyncOpUpdate},
+    instrument::Update,
+    register_metadata::NewMetadata,
+    resources::{self, ResourceUpdate},
+    tasks::{self, TaskDetails, TaskUpdate},
+    RegisterMetadata,
+};
+use prost_types::Timestamp;
+use std::collections::HashMap;
+
+/// Entity (task, resource or async op) with its latest stats
+struct Mirrored<T, S> {
+    entity: T,
+    stats: Option<S>,
+}
+
+/// Copy of the state of an application, as sent by its console-subscriber
+///
+/// Used to give the watchers joining late the same first update a direct
+/// connection would have given them. Completed entities are forgotten.
+#[derive(Default)]
+pub(crate) struct UpdateMirror {
+    now: Option<Timestamp>,
+    metadata: HashMap<u64, NewMetadata>,
+    tasks: HashMap<u64, Mirrored<tasks::Task, tasks::Stats>>,
+    resources: HashMap<u64, Mirrored<resources::Resource, resources::Stats>>,
+    async_ops: HashMap<u64, Mirrored<async_ops::AsyncOp, async_ops::Stats>>,
+    task_details: HashMap<u64, TaskDetails>,
+}
+
+/// Applies the new entities and stats of an update to the mirrored ones
+fn apply<T: Clone, S: Clone>(
+    mirrored: &mut HashMap<u64, Mirrored<T, S>>,
+    new_entities: &[T],
+    id: impl Fn(&T) -> Option<u64>,
+    stats_update: &HashMap<u64, S>,
+    is_dropped: impl Fn(&S) -> bool,
+) {
+    for entity in new_entities {
+        if let Some(id) = id(entity) {
+            mirrored.insert(
+                id,
+                Mirrored {
+                    entity: entity.clone(),
+                    stats: None,
+                },
+            );
+        }
+    }
+    for (id, stats) in stats_update {
+        if is_dropped(stats) {
+            mirrored.remove(id);
+        } else if let Some(entry) = mirrored.get_mut(id) {
+            entry.stats = Some(stats.clone());
+        }
+    }
+}
+
+/// Splits the mirrored entities into the new entities and the stats of an update
+fn snapshot<T: Clone, S: Clone>(
+    mirrored: &HashMap<u64, Mirrored<T, S>>,
+) -> (Vec<T>, HashMap<u64, S>) {
+    let entities = mirrored
+        .values()
+        .map(|entry| entry.entity.clone())
+        .collect();
+    let stats = mirrored
+        .iter()
+        .filter_map(|(id, entry)| entry.stats.clone().map(|stats| (*id, stats)))
+        .collect();
+    (entities, stats)
+}
+
+impl UpdateMirror {
+    pub fn apply_update(&mut self, update: &Update) {
+        self.now = update.now.or(self.now);
+
+        if let Some(new_metadata) = &update.new_metadata {
+            for metadata in &new_metadata.metadata {
+                if let Some(id) = metadata.id {
+                    self.metadata.insert(id.id, metadata.clone());
+                }
+            }
+        }
+        if let Some(task_update) = &update.task_update {
+            apply(
+                &mut self.tasks,
+                &task_update.new_tasks,
+                |task| task.id.map(|id| id.id),
+                &task_update.stats_update,
+                |stats| stats.dropped_at.is_some(),
+            );
+            self.task_details
+                .retain(|id, _| self.tasks.contains_key(id));
+        }
+        if let Some(resource_update) = &update.resource_update {
+            apply(
+                &mut self.resources,
+                &resource_update.new_resources,
+                |resource| resource.id.map(|id| id.id),
+                &resource_update.stats_update,
+                |stats| stats.dropped_at.is_some(),
+            );
+        }
+        if let Some(async_op_update) = &update.async_op_update {
+            apply(
+                &mut self.async_ops,
+                &async_op_update.new_async_ops,
+                |async_op| async_op.id.map(|id| id.id),
+                &async_op_update.stats_update,
+                |stats| stats.dropped_at.is_some(),
+            );
+        }
+    }
+
+    pub fn apply_task_details(&mut self, details: &TaskDetails) {
+        if let Some(task_id) = details.task_id {
+            if self.tasks.contains_key(&task_id.id) {
+                self.task_details.insert(task_id.id, details.clone());
+            }
+        }
+    }
+
+    pub fn task_details(&self, task_id: u64) -> Option<&TaskDetails> {
+        self.task_details.get(&task_id)
+    }
+
+    /// Returns an update holding the whole mirrored state
+    pub fn snapshot(&self) -> Update {
+        let (new_tasks, task_stats) = snapshot(&self.tasks);
+        let (new_resources, resource_stats) = snapshot(&self.resources);
+        let (new_async_ops, async_op_stats) = snapshot(&self.async_ops);
+
+        Update {
+            now: self.now,
+            task_update: Some(TaskUpdate {
+                new_tasks,
+                stats_update: task_stats,
+                dropped_events: 0,
+            }),
+            resource_update: Some(ResourceUpdate {
+                new_resources,
+                stats_update: resource_stats,
+                new_poll_ops: Vec::new(),
+                dropped_events: 0,
+            }),
+            async_op_update: Some(AsyncOpUpdate {
+                new_async_ops,
+                stats_update: async_op_stats,
+                dropped_events: 0,
+            }),
+            new_metadata: Some(RegisterMetadata {
+                metadata: self.metadata.values().cloned().collect(),
+            }),
+        }
+    }
+}
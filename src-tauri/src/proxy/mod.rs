@@ -0,0 +1,309 @@
+//! gRPC server re-exposing the `Instrument` service of a monitored application,
+//! so other consoles (eg: the tokio-console TUI) can watch it along with this one
+//!
+//! Watchers first receive the current state of the application, then the
+//! updates forwarded from its connection. Pausing is not supported, as it would
+//! pause the application for every watcher.
+
+mod mirror;
+
+use crate::endpoints::LocalServer;
+use crate::error::Error as TraceError;
+use crate::state_manager::StateManager;
+use console_api::{
+    instrument::{
+        instrument_server::{Instrument, InstrumentServer},
+        InstrumentRequest, PauseRequest, PauseResponse, ResumeRequest, ResumeResponse,
+        TaskDetailsRequest, Update,
+    },
+    tasks::TaskDetails,
+};
+use log::{info, warn};
+use mirror::UpdateMirror;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    mpsc, RwLock,
+};
+use tonic::{
+    async_trait,
+    codegen::{tokio_stream::wrappers::ReceiverStream, BoxStream},
+    transport::{server::TcpIncoming, Server},
+    Request, Response, Status,
+};
+use uuid::Uuid;
+
+/// Number of updates kept for the slowest watcher, watchers lagging
+/// further behind are disconnected
+const BROADCAST_CAPACITY: usize = 256;
+
+/// Number of messages buffered for every watcher
+const WATCHER_BUFFER: usize = 16;
+
+pub(crate) type UpdateReceiver = broadcast::Receiver<(Uuid, Arc<Update>)>;
+pub(crate) type TaskDetailsReceiver = broadcast::Receiver<(Uuid, Arc<TaskDetails>)>;
+
+/// Keeps the state of the applications and dispatches their
+/// updates to the watchers of the proxies
+///
+/// Only the applications with a running proxy are mirrored, or all of them
+/// for the collector, whose windows also receive their state.
+pub(crate) struct ProxyHub {
+    mirrors: RwLock<HashMap<Uuid, UpdateMirror>>,
+    mirror_all: AtomicBool,
+    updates: broadcast::Sender<(Uuid, Arc<Update>)>,
+    task_details: broadcast::Sender<(Uuid, Arc<TaskDetails>)>,
+}
+
+impl Default for ProxyHub {
+    fn default() -> Self {
+        Self {
+            mirrors: RwLock::new(HashMap::new()),
+            mirror_all: AtomicBool::new(false),
+            updates: broadcast::channel(BROADCAST_CAPACITY).0,
+            task_details: broadcast::channel(BROADCAST_CAPACITY).0,
+        }
+    }
+}
+
+impl ProxyHub {
+    /// Mirrors every application from now on
+    pub fn mirror_all(&self) {
+        self.mirror_all.store(true, Ordering::Relaxed);
+    }
+
+    /// Starts mirroring an application, from its next update holding its whole state
+    pub async fn start_mirror(&self, app_id: Uuid) {
+        self.mirrors.write().await.entry(app_id).or_default();
+    }
+
+    pub async fn stop_mirror(&self, app_id: Uuid) {
+        if !self.mirror_all.load(Ordering::Relaxed) {
+            self.mirrors.write().await.remove(&app_id);
+        }
+    }
+
+    /// Returns the mirror of the application, if it is mirrored
+    fn mirror<'a>(
+        &self,
+        mirrors: &'a mut HashMap<Uuid, UpdateMirror>,
+        app_id: Uuid,
+    ) -> Option<&'a mut UpdateMirror> {
+        if self.mirror_all.load(Ordering::Relaxed) {
+            Some(mirrors.entry(app_id).or_default())
+        } else {
+            mirrors.get_mut(&app_id)
+        }
+    }
+
+    pub async fn publish_update(&self, app_id: Uuid, update: &Update) {
+        // The lock is kept while sending, so subscribers do not miss
+        // or receive twice the update
+        let mut mirrors = self.mirrors.write().await;
+        if let Some(mirror) = self.mirror(&mut mirrors, app_id) {
+            mirror.apply_update(update);
+        }
+        if self.updates.receiver_count() > 0 {
+            self.updates.send((app_id, Arc::new(update.clone()))).ok();
+        }
+    }
+
+    pub async fn publish_task_details(&self, app_id: Uuid, details: &TaskDetails) {
+        let mut mirrors = self.mirrors.write().await;
+        if let Some(mirror) = self.mirror(&mut mirrors, app_id) {
+            mirror.apply_task_details(details);
+        }
+        if self.task_details.receiver_count() > 0 {
+            self.task_details
+                .send((app_id, Arc::new(details.clone())))
+                .ok();
+        }
+    }
+
+    pub async fn remove_app(&self, app_id: Uuid) {
+        self.mirrors.write().await.remove(&app_id);
+    }
+
+    /// Returns the current state of every application and receivers
+    /// of their next updates and task details
+    pub async fn subscribe_all(
+        &self,
+    ) -> (Vec<(Uuid, Update)>, UpdateReceiver, TaskDetailsReceiver) {
+        let mirrors = self.mirrors.read().await;
+        let snapshots = mirrors
+            .iter()
+            .map(|(app_id, mirror)| (*app_id, mirror.snapshot()))
+            .collect();
+        (
+            snapshots,
+            self.updates.subscribe(),
+            self.task_details.subscribe(),
+        )
+    }
+
+    /// Returns the current state of the application and a receiver of the next updates
+    async fn subscribe_updates(&self, app_id: Uuid) -> (Update, UpdateReceiver) {
+        let mirrors = self.mirrors.read().await;
+        let snapshot = mirrors
+            .get(&app_id)
+            .map(UpdateMirror::snapshot)
+            .unwrap_or_default();
+        (snapshot, self.updates.subscribe())
+    }
+
+    async fn subscribe_task_details(
+        &self,
+        app_id: Uuid,
+        task_id: u64,
+    ) -> (Option<TaskDetails>, TaskDetailsReceiver) {
+        let mirrors = self.mirrors.read().await;
+        let details = mirrors
+            .get(&app_id)
+            .and_then(|mirror| mirror.task_details(task_id))
+            .cloned();
+        (details, self.task_details.subscribe())
+    }
+}
+
+/// Forwards the messages of an application to a watcher, until one of them leaves
+fn forward<T: Clone + Send + Sync + 'static>(
+    first: Option<T>,
+    mut receiver: broadcast::Receiver<(Uuid, Arc<T>)>,
+    keep: impl Fn(Uuid, &T) -> bool + Send + 'static,
+) -> BoxStream<T> {
+    let (sender, watcher) = mpsc::channel(WATCHER_BUFFER);
+
+    tokio::spawn(async move {
+        if let Some(first) = first {
+            if sender.send(Ok(first)).await.is_err() {
+                return;
+            }
+        }
+        loop {
+            let message = match receiver.recv().await {
+                Ok((app_id, message)) if keep(app_id, &message) => message,
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Proxy watcher fell behind by {skipped} messages, disconnecting it");
+                    let status = Status::data_loss(
+                        "fell behind the application updates, reconnect to receive its state",
+                    );
+                    sender.send(Err(status)).await.ok();
+                    break;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            if sender.send(Ok(message.as_ref().clone())).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    Box::pin(ReceiverStream::new(watcher))
+}
+
+/// `Instrument` service of one application
+pub(crate) struct InstrumentProxy {
+    app_id: Uuid,
+    state_manager: Arc<StateManager>,
+}
+
+#[async_trait]
+impl Instrument for InstrumentProxy {
+    type WatchUpdatesStream = BoxStream<Update>;
+    type WatchTaskDetailsStream = BoxStream<TaskDetails>;
+
+    async fn watch_updates(
+        &self,
+        request: Request<InstrumentRequest>,
+    ) -> Result<Response<Self::WatchUpdatesStream>, Status> {
+        info!(
+            "Proxy watcher {:?} connected to application {}",
+            request.remote_addr(),
+            self.app_id
+        );
+        let app_id = self.app_id;
+        let (snapshot, updates) = self.state_manager.proxy_hub.subscribe_updates(app_id).await;
+
+        Ok(Response::new(forward(
+            Some(snapshot),
+            updates,
+            move |update_app_id, _| update_app_id == app_id,
+        )))
+    }
+
+    async fn watch_task_details(
+        &self,
+        request: Request<TaskDetailsRequest>,
+    ) -> Result<Response<Self::WatchTaskDetailsStream>, Status> {
+        let Some(task_id) = request.into_inner().id.map(|id| id.id) else {
+            return Err(Status::invalid_argument("the task id is missing"));
+        };
+
+        let app_id = self.app_id;
+        let (details, receiver) = self
+            .state_manager
+            .proxy_hub
+            .subscribe_task_details(app_id, task_id)
+            .await;
+        // The details are only sent by the application once requested
+        self.state_manager
+            .watch_task_details(app_id, &[task_id])
+            .await;
+
+        Ok(Response::new(forward(
+            details,
+            receiver,
+            move |details_app_id, details| {
+                details_app_id == app_id && details.task_id.map(|id| id.id) == Some(task_id)
+            },
+        )))
+    }
+
+    async fn pause(&self, _: Request<PauseRequest>) -> Result<Response<PauseResponse>, Status> {
+        Err(Status::unimplemented(
+            "pausing is not supported through the proxy",
+        ))
+    }
+
+    async fn resume(&self, _: Request<ResumeRequest>) -> Result<Response<ResumeResponse>, Status> {
+        Err(Status::unimplemented(
+            "resuming is not supported through the proxy",
+        ))
+    }
+}
+
+/// Starts the proxy of an application on the given port
+///
+/// # Error
+///
+/// If the port cannot be listened on, an error will be returned
+pub(crate) async fn start_proxy(
+    port: u16,
+    app_id: Uuid,
+    state_manager: Arc<StateManager>,
+) -> Result<LocalServer, TraceError> {
+    let service = InstrumentServer::new(InstrumentProxy {
+        app_id,
+        state_manager,
+    });
+
+    LocalServer::start_with(port, |listener, shutdown| async move {
+        let incoming =
+            TcpIncoming::from_listener(listener, true, None).map_err(anyhow::Error::msg)?;
+        Server::builder()
+            .add_service(service)
+            .serve_with_incoming_shutdown(incoming, async {
+                shutdown.await.ok();
+            })
+            .await
+            .map_err(anyhow::Error::from)
+    })
+    .await
+}
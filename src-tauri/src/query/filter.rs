@@ -0,0 +1,438 @@
+//! Parser and evaluator of the task filter expressions
+//!
+//! ```text
+//! expression := and ("or" and)*
+//! and        := not ("and" not)*
+//! not        := "not" not | "(" expression ")" | comparison
+//! comparison := field operator value
+//! operator   := "=" | "!=" | ">" | ">=" | "<" | "<=" | "~" | "!~"
+//! ```
+//!
+//! Values are numbers, durations (`10ms`, `1.5s`), quoted strings or bare
+//! words, eg: `busy > 10ms and polls < 5 and not name ~ "worker"`.
+//! Texts are compared ignoring the case.
+
+use super::{FieldValue, TaskField};
+use crate::domain::Task;
+use crate::error::Error as TraceError;
+
+/// Maximum nesting of the parentheses and `not`, deeper expressions
+/// are rejected instead of overflowing the stack
+const MAX_NESTING: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Operator {
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Contains,
+    NotContains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Text(String),
+    Operator(Operator),
+    OpenParenthesis,
+    CloseParenthesis,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Value {
+    /// Lowercased when parsed
+    Text(String),
+    Number(f64),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Compare {
+        field: TaskField,
+        operator: Operator,
+        value: Value,
+    },
+}
+
+impl Filter {
+    pub fn parse(expression: &str) -> Result<Filter, TraceError> {
+        let tokens = tokenize(expression)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            position: 0,
+            nesting: 0,
+        };
+        let filter = parser.expression()?;
+        match parser.peek() {
+            None => Ok(filter),
+            Some(token) => Err(invalid(format!("unexpected {token:?}"))),
+        }
+    }
+
+    pub fn matches(&self, task: &Task) -> bool {
+        match self {
+            Filter::And(left, right) => left.matches(task) && right.matches(task),
+            Filter::Or(left, right) => left.matches(task) || right.matches(task),
+            Filter::Not(filter) => !filter.matches(task),
+            Filter::Compare {
+                field,
+                operator,
+                value,
+            } => compare(field.value(task), *operator, value),
+        }
+    }
+}
+
+fn compare(field_value: FieldValue, operator: Operator, value: &Value) -> bool {
+    match (field_value, value) {
+        (FieldValue::Number(Some(number)), Value::Number(value)) => match operator {
+            Operator::Equal => number == *value,
+            Operator::NotEqual => number != *value,
+            Operator::Greater => number > *value,
+            Operator::GreaterOrEqual => number >= *value,
+            Operator::Less => number < *value,
+            Operator::LessOrEqual => number <= *value,
+            Operator::Contains | Operator::NotContains => false,
+        },
+        (FieldValue::Text(Some(text)), Value::Text(value)) => {
+            let text = text.to_lowercase();
+            match operator {
+                Operator::Equal => text == *value,
+                Operator::NotEqual => text != *value,
+                Operator::Contains => text.contains(value.as_str()),
+                Operator::NotContains => !text.contains(value.as_str()),
+                _ => false,
+            }
+        }
+        // Missing values only match the negative operators
+        _ => matches!(operator, Operator::NotEqual | Operator::NotContains),
+    }
+}
+
+fn invalid(message: impl Into<String>) -> TraceError {
+    TraceError::InvalidQuery(message.into())
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, TraceError> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::OpenParenthesis);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::CloseParenthesis);
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some(next) if next == c => break,
+                        Some(next) => text.push(next),
+                        None => return Err(invalid("unterminated string")),
+                    }
+                }
+                tokens.push(Token::Text(text));
+            }
+            '=' | '!' | '>' | '<' | '~' => {
+                chars.next();
+                let followed_by_equal = chars.next_if_eq(&'=').is_some();
+                let operator = match (c, followed_by_equal) {
+                    ('=', _) => Operator::Equal,
+                    ('!', true) => Operator::NotEqual,
+                    ('!', false) if chars.next_if_eq(&'~').is_some() => Operator::NotContains,
+                    ('>', true) => Operator::GreaterOrEqual,
+                    ('>', false) => Operator::Greater,
+                    ('<', true) => Operator::LessOrEqual,
+                    ('<', false) => Operator::Less,
+                    ('~', false) => Operator::Contains,
+                    _ => return Err(invalid(format!("unknown operator starting with {c}"))),
+                };
+                tokens.push(Token::Operator(operator));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(next) =
+                    chars.next_if(|next| !next.is_whitespace() && !"()=!<>~\"'".contains(*next))
+                {
+                    word.push(next);
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    nesting: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        token
+    }
+
+    fn next_if_keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expression(&mut self) -> Result<Filter, TraceError> {
+        let mut filter = self.and()?;
+        while self.next_if_keyword("or") {
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<Filter, TraceError> {
+        let mut filter = self.not()?;
+        while self.next_if_keyword("and") {
+            filter = Filter::And(Box::new(filter), Box::new(self.not()?));
+        }
+        Ok(filter)
+    }
+
+    fn not(&mut self) -> Result<Filter, TraceError> {
+        if self.next_if_keyword("not") {
+            return Ok(Filter::Not(Box::new(self.nested(Self::not)?)));
+        }
+
+        if self.peek() == Some(&Token::OpenParenthesis) {
+            self.position += 1;
+            let filter = self.nested(Self::expression)?;
+            return match self.next() {
+                Some(Token::CloseParenthesis) => Ok(filter),
+                _ => Err(invalid("missing closing parenthesis")),
+            };
+        }
+
+        self.comparison()
+    }
+
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Filter, TraceError>,
+    ) -> Result<Filter, TraceError> {
+        if self.nesting == MAX_NESTING {
+            return Err(invalid(format!(
+                "expression nested more than {MAX_NESTING} times"
+            )));
+        }
+        self.nesting += 1;
+        let filter = parse(self);
+        self.nesting -= 1;
+        filter
+    }
+
+    fn comparison(&mut self) -> Result<Filter, TraceError> {
+        let field = match self.next() {
+            Some(Token::Word(name)) => {
+                TaskField::parse(name).ok_or_else(|| invalid(format!("unknown field {name}")))?
+            }
+            token => return Err(invalid(format!("expected a field, found {token:?}"))),
+        };
+
+        let operator = match self.next() {
+            Some(Token::Operator(operator)) => *operator,
+            token => return Err(invalid(format!("expected an operator, found {token:?}"))),
+        };
+
+        let value = match (self.next(), field.is_numeric()) {
+            (Some(Token::Word(word)), true) => Value::Number(parse_number(field, word)?),
+            (Some(Token::Word(word)) | Some(Token::Text(word)), false) => {
+                Value::Text(word.to_lowercase())
+            }
+            (token, _) => return Err(invalid(format!("expected a value, found {token:?}"))),
+        };
+
+        let valid_operator = match value {
+            Value::Number(_) => !matches!(operator, Operator::Contains | Operator::NotContains),
+            Value::Text(_) => matches!(
+                operator,
+                Operator::Equal | Operator::NotEqual | Operator::Contains | Operator::NotContains
+            ),
+        };
+        if !valid_operator {
+            return Err(invalid(format!(
+                "operator {operator:?} cannot be used with {}",
+                field.name()
+            )));
+        }
+
+        Ok(Filter::Compare {
+            field,
+            operator,
+            value,
+        })
+    }
+}
+
+/// Parses a number, durations are converted to nanoseconds
+/// and are considered milliseconds if no unit is given
+fn parse_number(field: TaskField, word: &str) -> Result<f64, TraceError> {
+    let sign = usize::from(word.starts_with('-'));
+    let split = word[sign..]
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .map_or(word.len(), |split| sign + split);
+    let (number, unit) = word.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| invalid(format!("{word} is not a number")))?;
+
+    if !field.is_duration() {
+        return match unit {
+            "" => Ok(number),
+            _ => Err(invalid(format!("{} does not have a unit", field.name()))),
+        };
+    }
+
+    let nanoseconds = match unit {
+        "ns" => 1.0,
+        "us" | "µs" => 1e3,
+        "" | "ms" => 1e6,
+        "s" => 1e9,
+        "m" => 60e9,
+        "h" => 3600e9,
+        _ => return Err(invalid(format!("unknown duration unit {unit}"))),
+    };
+    Ok(number * nanoseconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::TaskStats;
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    fn task(name: &str, polls: u64, busy: Duration) -> Task {
+        Task {
+            app_id: Uuid::nil(),
+            id: 1,
+            tid: None,
+            name: Some(name.to_owned()),
+            kind: None,
+            location: None,
+            stats: TaskStats {
+                polls,
+                busy_time: busy,
+                ..TaskStats::default()
+            },
+        }
+    }
+
+    fn matches(expression: &str, task: &Task) -> bool {
+        Filter::parse(expression).unwrap().matches(task)
+    }
+
+    fn error(expression: &str) -> String {
+        match Filter::parse(expression) {
+            Err(TraceError::InvalidQuery(message)) => message,
+            result => panic!("{expression} should be invalid, parsed as {result:?}"),
+        }
+    }
+
+    #[test]
+    fn compares_numbers_and_durations() {
+        let task = task("worker", 3, Duration::from_millis(20));
+        assert!(matches("polls = 3", &task));
+        assert!(matches("polls >= 3 and polls <= 3", &task));
+        assert!(!matches("polls > 3", &task));
+        assert!(matches("busy > 10ms", &task));
+        assert!(matches("busy < 0.5s", &task));
+        // Durations without a unit are milliseconds
+        assert!(matches("busy = 20", &task));
+        assert!(matches("polls > -1", &task));
+        assert!(matches("busy > -1.5s", &task));
+    }
+
+    #[test]
+    fn compares_texts_ignoring_the_case() {
+        let task = task("Worker Pool", 0, Duration::ZERO);
+        assert!(matches("name = \"worker pool\"", &task));
+        assert!(matches("name ~ POOL", &task));
+        assert!(matches("name !~ 'queue'", &task));
+        assert!(!matches("name != 'WORKER POOL'", &task));
+        // Missing values only match the negative operators
+        assert!(matches("kind != blocking", &task));
+        assert!(!matches("kind ~ blocking", &task));
+    }
+
+    #[test]
+    fn applies_the_precedence() {
+        let task = task("worker", 3, Duration::ZERO);
+        // `and` binds tighter than `or`
+        assert!(matches("polls = 1 and polls = 2 or polls = 3", &task));
+        assert!(matches("polls = 3 or polls = 1 and polls = 2", &task));
+        assert!(!matches("(polls = 3 or polls = 1) and polls = 2", &task));
+        // `not` binds tighter than `and`
+        assert!(!matches("not polls = 3 and name = worker", &task));
+        assert!(matches("not (polls = 3 and name = other)", &task));
+        assert!(matches("NOT NOT polls = 3", &task));
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        assert_eq!(error("size > 3"), "unknown field size");
+        assert_eq!(
+            error("polls 3"),
+            "expected an operator, found Some(Word(\"3\"))"
+        );
+        assert_eq!(error("polls >"), "expected a value, found None");
+        assert_eq!(error("polls > 3ms"), "polls does not have a unit");
+        assert_eq!(error("busy > 3d"), "unknown duration unit d");
+        assert_eq!(error("polls > -"), "- is not a number");
+        assert_eq!(
+            error("polls ~ 3"),
+            "operator Contains cannot be used with polls"
+        );
+        assert_eq!(
+            error("name > a"),
+            "operator Greater cannot be used with name"
+        );
+        assert_eq!(error("name = \"a"), "unterminated string");
+        assert_eq!(error("polls !> 3"), "unknown operator starting with !");
+        assert_eq!(error("(polls > 3"), "missing closing parenthesis");
+        assert_eq!(error("polls > 3)"), "unexpected CloseParenthesis");
+    }
+
+    #[test]
+    fn limits_the_nesting() {
+        let nested =
+            |depth: usize| format!("{}polls > 3{}", "(not ".repeat(depth), ")".repeat(depth));
+        assert!(Filter::parse(&nested(MAX_NESTING / 2)).is_ok());
+        assert!(error(&nested(MAX_NESTING)).contains("nested more than"));
+        assert!(error(&"not ".repeat(100_000)).contains("nested more than"));
+        assert!(error(&"(".repeat(100_000)).contains("nested more than"));
+    }
+}
@@ -0,0 +1,217 @@
+//! Filtering, sorting and pagination of the tasks, so that the frontend
+//! only receives the tasks it displays
+
+mod filter;
+
+use crate::domain::Task;
+use crate::error::Error as TraceError;
+use filter::Filter;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, sync::Arc};
+
+/// Task attribute that can be used for filtering and sorting
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum TaskField {
+    Id,
+    Tid,
+    Name,
+    Kind,
+    App,
+    State,
+    Location,
+    Polls,
+    Wakes,
+    SelfWakes,
+    WakerClones,
+    WakerDrops,
+    Busy,
+    Scheduled,
+}
+
+pub(crate) enum FieldValue {
+    Text(Option<String>),
+    Number(Option<f64>),
+}
+
+impl TaskField {
+    const ALL: [TaskField; 14] = [
+        TaskField::Id,
+        TaskField::Tid,
+        TaskField::Name,
+        TaskField::Kind,
+        TaskField::App,
+        TaskField::State,
+        TaskField::Location,
+        TaskField::Polls,
+        TaskField::Wakes,
+        TaskField::SelfWakes,
+        TaskField::WakerClones,
+        TaskField::WakerDrops,
+        TaskField::Busy,
+        TaskField::Scheduled,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TaskField::Id => "id",
+            TaskField::Tid => "tid",
+            TaskField::Name => "name",
+            TaskField::Kind => "kind",
+            TaskField::App => "app",
+            TaskField::State => "state",
+            TaskField::Location => "location",
+            TaskField::Polls => "polls",
+            TaskField::Wakes => "wakes",
+            TaskField::SelfWakes => "self_wakes",
+            TaskField::WakerClones => "waker_clones",
+            TaskField::WakerDrops => "waker_drops",
+            TaskField::Busy => "busy",
+            TaskField::Scheduled => "scheduled",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<TaskField> {
+        Self::ALL
+            .into_iter()
+            .find(|field| field.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        !matches!(
+            self,
+            TaskField::Name
+                | TaskField::Kind
+                | TaskField::App
+                | TaskField::State
+                | TaskField::Location
+        )
+    }
+
+    pub fn is_duration(&self) -> bool {
+        matches!(self, TaskField::Busy | TaskField::Scheduled)
+    }
+
+    /// Value of the field for a task, durations are in nanoseconds
+    pub fn value(&self, task: &Task) -> FieldValue {
+        let stats = &task.stats;
+        match self {
+            TaskField::Id => FieldValue::Number(Some(task.id as f64)),
+            TaskField::Tid => FieldValue::Number(task.tid.map(|tid| tid as f64)),
+            TaskField::Name => FieldValue::Text(task.name.clone()),
+            TaskField::Kind => FieldValue::Text(task.kind.clone()),
+            TaskField::App => FieldValue::Text(Some(task.app_id.to_string())),
+            TaskField::State => FieldValue::Text(Some(task.state().as_str().to_owned())),
+            TaskField::Location => FieldValue::Text(task.location.clone()),
+            TaskField::Polls => FieldValue::Number(Some(stats.polls as f64)),
+            TaskField::Wakes => FieldValue::Number(Some(stats.wakes as f64)),
+            TaskField::SelfWakes => FieldValue::Number(Some(stats.self_wakes as f64)),
+            TaskField::WakerClones => FieldValue::Number(Some(stats.waker_clones as f64)),
+            TaskField::WakerDrops => FieldValue::Number(Some(stats.waker_drops as f64)),
+            TaskField::Busy => FieldValue::Number(Some(stats.busy_time.as_nanos() as f64)),
+            TaskField::Scheduled => {
+                FieldValue::Number(Some(stats.scheduled_time.as_nanos() as f64))
+            }
+        }
+    }
+}
+
+/// Compares two values of the same field, missing values are placed last
+fn compare_values(left: FieldValue, right: FieldValue) -> Ordering {
+    match (left, right) {
+        (FieldValue::Number(left), FieldValue::Number(right)) => match (left, right) {
+            (Some(left), Some(right)) => left.total_cmp(&right),
+            (left, right) => right.is_some().cmp(&left.is_some()),
+        },
+        (FieldValue::Text(left), FieldValue::Text(right)) => match (left, right) {
+            (Some(left), Some(right)) => left
+                .chars()
+                .flat_map(char::to_lowercase)
+                .cmp(right.chars().flat_map(char::to_lowercase)),
+            (left, right) => right.is_some().cmp(&left.is_some()),
+        },
+        _ => Ordering::Equal,
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct SortKey {
+    pub field: String,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct TaskQuery {
+    /// Filter expression, eg: `busy > 10ms and polls < 5`
+    pub filter: Option<String>,
+    /// Sort keys, by priority
+    #[serde(default)]
+    pub sort: Vec<SortKey>,
+    #[serde(default)]
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TaskPage {
+    /// Number of tasks matching the filter
+    pub total: usize,
+    pub tasks: Vec<Arc<Task>>,
+}
+
+/// Filters, sorts and paginates the tasks
+///
+/// # Error
+///
+/// If the filter expression or a sort key is invalid, an error is returned
+pub fn query_tasks(tasks: Vec<Arc<Task>>, query: &TaskQuery) -> Result<TaskPage, TraceError> {
+    let filter = query
+        .filter
+        .as_deref()
+        .filter(|filter| !filter.trim().is_empty())
+        .map(Filter::parse)
+        .transpose()?;
+
+    let sort_keys = query
+        .sort
+        .iter()
+        .map(|key| {
+            TaskField::parse(&key.field)
+                .map(|field| (field, key.descending))
+                .ok_or_else(|| TraceError::InvalidQuery(format!("unknown field {}", key.field)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut tasks: Vec<Arc<Task>> = match filter {
+        Some(filter) => tasks
+            .into_iter()
+            .filter(|task| filter.matches(task))
+            .collect(),
+        None => tasks,
+    };
+
+    tasks.sort_by(|left, right| {
+        sort_keys
+            .iter()
+            .map(|(field, descending)| {
+                let ordering = compare_values(field.value(left), field.value(right));
+                if *descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .find(|ordering| ordering.is_ne())
+            // Keep a stable order between queries
+            .unwrap_or_else(|| (left.app_id, left.id).cmp(&(right.app_id, right.id)))
+    });
+
+    let total = tasks.len();
+    let tasks = tasks
+        .into_iter()
+        .skip(query.offset)
+        .take(query.limit.unwrap_or(usize::MAX))
+        .collect();
+
+    Ok(TaskPage { total, tasks })
+}
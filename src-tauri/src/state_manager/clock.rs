@@ -0,0 +1,124 @@
+//! Conversion between the clock of an application and the local clock
+//!
+//! Timestamps sent by an application are in its own clock, which may be
+//! skewed from the local one. The offset between the two clocks is estimated
+//! from the `now` timestamp of every update.
+
+use log::info;
+use std::time::{Duration, SystemTime};
+
+/// Offset changes larger than this are clock adjustments (eg: NTP steps)
+/// rather than network jitter, the offset is then reset
+const MAX_OFFSET_JUMP: Duration = Duration::from_secs(1);
+
+/// Weight of a new sample in the estimated offset, smooths the network jitter
+const OFFSET_SMOOTHING: f64 = 0.125;
+
+/// Clock of a connected application
+#[derive(Clone, Debug)]
+pub(crate) struct Clock {
+    /// Local time minus application time, in nanoseconds
+    offset: f64,
+    /// Time of the last update, in the application's clock
+    last_update: SystemTime,
+    /// Number of times the application restarted, as detected from its tasks
+    restarts: u32,
+    /// Recorded applications are replayed at once, their clock stops at the last update
+    recorded: bool,
+    /// No update is received until reconnected (eg: disconnected or exited),
+    /// the clock stops at the last update
+    paused: bool,
+}
+
+impl Clock {
+    pub fn new(remote_now: SystemTime, local_now: SystemTime) -> Self {
+        Self {
+            offset: offset_nanos(remote_now, local_now),
+            last_update: remote_now,
+            restarts: 0,
+            recorded: false,
+            paused: false,
+        }
+    }
+
+    /// Clock of a recording, its timestamps are kept as they are
+    pub fn recorded(remote_now: SystemTime) -> Self {
+        Self {
+            offset: 0.0,
+            last_update: remote_now,
+            restarts: 0,
+            recorded: true,
+            paused: false,
+        }
+    }
+
+    /// Updates the offset out of the time of an update, received at `local_now`
+    pub fn sync(&mut self, remote_now: SystemTime, local_now: SystemTime) {
+        let sample = offset_nanos(remote_now, local_now);
+        if self.recorded {
+            self.last_update = self.last_update.max(remote_now);
+            return;
+        }
+        if self.paused {
+            // The connection was reset, the application may be another process
+            self.paused = false;
+            self.offset = sample;
+        } else if (sample - self.offset).abs() > MAX_OFFSET_JUMP.as_nanos() as f64 {
+            info!("Application clock jumped, resetting its offset");
+            self.offset = sample;
+        } else {
+            self.offset += (sample - self.offset) * OFFSET_SMOOTHING;
+        }
+        self.last_update = remote_now;
+    }
+
+    /// Stops the clock at the last update, until the next one is received
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Counts a restart of the application
+    pub fn restarted(&mut self) {
+        self.restarts += 1;
+    }
+
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+
+    /// Time of the last update, in the application's clock
+    pub fn last_update(&self) -> SystemTime {
+        self.last_update
+    }
+
+    /// Converts a timestamp of the application to the local clock
+    pub fn to_local(&self, remote: SystemTime) -> SystemTime {
+        shift(remote, self.offset)
+    }
+
+    /// Estimates the time of the application at the local time `local_now`,
+    /// keeps increasing while connected and no update is received
+    pub fn remote_now(&self, local_now: SystemTime) -> SystemTime {
+        if self.recorded || self.paused {
+            return self.last_update;
+        }
+        // The application is never considered behind its last update
+        shift(local_now, -self.offset).max(self.last_update)
+    }
+}
+
+fn offset_nanos(remote: SystemTime, local: SystemTime) -> f64 {
+    match local.duration_since(remote) {
+        Ok(ahead) => ahead.as_nanos() as f64,
+        Err(error) => -(error.duration().as_nanos() as f64),
+    }
+}
+
+fn shift(time: SystemTime, nanos: f64) -> SystemTime {
+    let delta = Duration::from_nanos(nanos.abs() as u64);
+    if nanos >= 0.0 {
+        time + delta
+    } else {
+        time - delta
+    }
+}
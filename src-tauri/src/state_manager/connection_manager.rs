@@ -1,9 +1,22 @@
 #![allow(unused)]
 
-use crate::error::Error as TraceError;
-use console_api::instrument::{instrument_client::InstrumentClient, InstrumentRequest, Update};
+use super::metadata::MetadataInterceptor;
+use super::pipeline::{EventSender, IngestionStats};
+use super::pre_connect::{self, HookProcess};
+use crate::{domain::application::PreConnectHook, error::Error as TraceError};
+use console_api::{
+    instrument::{
+        instrument_client::InstrumentClient, InstrumentRequest, TaskDetailsRequest, Update,
+    },
+    tasks::TaskDetails,
+};
 use log::{error, info, warn};
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    sync::Arc,
+    time::Duration,
+};
 use tauri::Url;
 use tokio::{
     select,
@@ -11,13 +24,37 @@ use tokio::{
         mpsc::{self, Sender},
         RwLock,
     },
+    task::{AbortHandle, JoinSet},
     time::sleep,
 };
-use tonic::{transport::Endpoint, Streaming};
+use tonic::{
+    service::interceptor::InterceptedService,
+    transport::{Channel, Endpoint},
+    Streaming,
+};
 use uuid::Uuid;
 
+/// Client sending the custom metadata of the application with every request
+type Client = InstrumentClient<InterceptedService<Channel, MetadataInterceptor>>;
+
+/// Delay before trying to connect again to an application
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Delay before running again a pre-connect command that failed
+const PRE_CONNECT_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Tasks whose details are watched at the same time on a connection
+const MAX_WATCHED_TASKS: usize = 64;
+
 pub enum Command {
     Disconnect,
+    /// Starts receiving the details (eg: poll times histogram) of a task,
+    /// the least recently watched task stops being watched past
+    /// `MAX_WATCHED_TASKS`
+    WatchTaskDetails(u64),
+    /// Subscribes to the updates again, the next one then holds the whole
+    /// state of the application
+    WatchUpdates,
 }
 
 #[non_exhaustive]
@@ -25,6 +62,7 @@ pub enum Event {
     Connecting,
     Connected,
     Update(Update),
+    TaskDetails(TaskDetails),
     Error(TraceError),
     Disconnected,
 }
@@ -35,19 +73,66 @@ pub struct Connection {
     pub commands: Sender<Command>,
 }
 
+/// Streams of task details of a connection, aborted when dropped
+#[derive(Default)]
+struct DetailsWatchers {
+    streams: JoinSet<u64>,
+    /// Watched tasks, the least recently requested first
+    watched: VecDeque<(u64, AbortHandle)>,
+}
+
+impl DetailsWatchers {
+    /// Starts streaming the details of a task unless already watched, stops
+    /// streaming the least recently requested task past `MAX_WATCHED_TASKS`
+    fn watch(&mut self, task_id: u64, details: impl Future<Output = u64> + Send + 'static) {
+        if let Some(position) = self.watched.iter().position(|(id, _)| *id == task_id) {
+            if let Some(watched) = self.watched.remove(position) {
+                self.watched.push_back(watched);
+            }
+            return;
+        }
+
+        if self.watched.len() >= MAX_WATCHED_TASKS {
+            if let Some((_, stream)) = self.watched.pop_front() {
+                stream.abort();
+            }
+        }
+        let stream = self.streams.spawn(details);
+        self.watched.push_back((task_id, stream));
+    }
+
+    /// Resolves when the details stream of a task ends, never resolves
+    /// without any stream
+    async fn ended(&mut self) {
+        match self.streams.join_next_with_id().await {
+            Some(Ok((id, _))) => self.watched.retain(|(_, stream)| stream.id() != id),
+            // Aborted streams were already removed
+            Some(Err(_)) => {}
+            None => std::future::pending().await,
+        }
+    }
+}
+
 pub struct ConnectionManager {
-    updates_sender: Sender<(Uuid, Event)>,
+    updates_sender: EventSender,
     active_connections: Arc<RwLock<HashMap<Uuid, tokio::task::JoinHandle<()>>>>,
 }
 
 impl ConnectionManager {
-    pub fn new(updates_sender: Sender<(Uuid, Event)>) -> Self {
+    pub fn new(updates_sender: EventSender) -> Self {
         Self {
             updates_sender,
             active_connections: Arc::new(RwLock::new(HashMap::new())),
         }
     }
-    pub async fn connect_app(&self, uuid: Uuid, url: Url) -> Result<Connection, TraceError> {
+    /// Connects to an application, running its pre-connect command first if any
+    pub async fn connect_app(
+        &self,
+        uuid: Uuid,
+        url: Url,
+        pre_connect: Option<PreConnectHook>,
+        metadata: MetadataInterceptor,
+    ) -> Result<Connection, TraceError> {
         let (command_sender, mut command_receiver) = mpsc::channel(100);
         let connection = Connection {
             commands: command_sender,
@@ -61,33 +146,71 @@ impl ConnectionManager {
         }
 
         let connection_task = tokio::task::spawn(async move {
+            // Details requested while not connected, watched once connected
+            let mut pending_watches = Vec::new();
+            // Pre-connect command, kept running across the connection attempts
+            let mut hook = None;
+
             'connection: loop {
                 // TODO: to check who will listen on this stream; enventually in the UI to give feedback to the user while trying to connect
-                updates_sender.send((uuid, Event::Connecting)).await.ok();
+                updates_sender.send(uuid, Event::Connecting);
 
-                // Connect the app
+                // Connect the app, the pre-connect command is kept running while connected
+                // Pinned once, the commands received meanwhile do not restart it
+                let mut connecting = Box::pin(Self::prepare_and_connect(
+                    &url,
+                    pre_connect.as_ref(),
+                    &mut hook,
+                    &metadata,
+                ));
                 let connection = 'connect: loop {
                     select! {
-                        connection = Self::connect_to_app(&url) => {
+                        connection = &mut connecting => {
                             // m-am conectat, astept comenzi mai jos
                             break 'connect connection;
                         }
                         command = command_receiver.recv() => {
                             match command {
-                                Some(Command::Disconnect) | None => break 'connection
+                                Some(Command::Disconnect) | None => break 'connection,
+                                // Details can only be watched once connected
+                                Some(Command::WatchTaskDetails(task_id)) => {
+                                    if pending_watches.len() >= MAX_WATCHED_TASKS {
+                                        pending_watches.remove(0);
+                                    }
+                                    pending_watches.push(task_id);
+                                    continue 'connect;
+                                }
+                                // The first update holds the whole state
+                                Some(Command::WatchUpdates) => continue 'connect,
                             }
                         }
                     };
                 };
+                // Releases the pre-connect command
+                drop(connecting);
 
                 // Vad daca primesc comenzi pt aplicatie (gen disconnect/disable)
                 // Check connection
                 match connection {
-                    Ok(mut update_stream) => {
+                    Ok((mut client, mut update_stream)) => {
                         info!("Successfully connected to application with url {url}");
 
                         // TODO: who listens here?
-                        updates_sender.send((uuid, Event::Connected)).await.ok();
+                        updates_sender.send(uuid, Event::Connected);
+
+                        // Streams of task details, aborted when the connection is lost
+                        let mut details_watchers = DetailsWatchers::default();
+                        for task_id in pending_watches.drain(..) {
+                            details_watchers.watch(
+                                task_id,
+                                Self::watch_task_details(
+                                    uuid,
+                                    task_id,
+                                    client.clone(),
+                                    updates_sender.clone(),
+                                ),
+                            );
+                        }
 
                         // Wait for events
                         loop {
@@ -98,21 +221,49 @@ impl ConnectionManager {
                                         Ok(message) => {
                                             if let Some(update) = message {
                                                 info!("Received an update about application with url {url}");
-                                                updates_sender.send((uuid, Event::Update(update))).await.ok();
+                                                updates_sender.send(uuid, Event::Update(update));
                                             }
                                         }
-                                        Err(_error) => {
-                                            // TODO report error
-                                            // for now we disconnect
+                                        Err(status) => {
+                                            warn!("Lost the update stream of application with url {url} due to {status:?}");
+                                            updates_sender.send(uuid, Event::Error(status.into()));
                                             continue 'connection;
                                         }
                                     }
                                 }
+                                // The connection is lost once the pre-connect command exits
+                                error = Self::hook_failure(&mut hook) => {
+                                    error!("Pre-connect command of application with url {url} stopped: {error}");
+                                    updates_sender.send(uuid, Event::Error(error));
+                                    hook = None;
+                                    continue 'connection;
+                                }
+                                // Task details streams end with the task
+                                () = details_watchers.ended() => {}
                                 // Wait for external commands
                                 command = command_receiver.recv() => {
                                     if let Some(command) = command {
                                         match command {
                                             Command::Disconnect => break 'connection,
+                                            Command::WatchTaskDetails(task_id) => {
+                                                details_watchers.watch(task_id, Self::watch_task_details(
+                                                    uuid,
+                                                    task_id,
+                                                    client.clone(),
+                                                    updates_sender.clone(),
+                                                ));
+                                            }
+                                            Command::WatchUpdates => {
+                                                let request = tonic::Request::new(InstrumentRequest {});
+                                                match client.watch_updates(request).await {
+                                                    Ok(response) => *update_stream = response.into_inner(),
+                                                    Err(status) => {
+                                                        warn!("Cannot watch the updates of application with url {url} again due to {status:?}");
+                                                        updates_sender.send(uuid, Event::Error(status.into()));
+                                                        continue 'connection;
+                                                    }
+                                                }
+                                            }
                                         }
                                     } else {
                                         // Command stream is closed so we exit
@@ -124,18 +275,21 @@ impl ConnectionManager {
                     }
                     Err(error) => {
                         error!("Could not connect to application with url {url} due to {error:?}");
-                        updates_sender
-                            .send((uuid, Event::Error(TraceError::Anyhow(error.into()))))
-                            .await
-                            .ok();
+                        // Pre-connect commands are slower to restart, give them more time
+                        let retry_delay = match error {
+                            TraceError::PreConnectFailed { .. } => PRE_CONNECT_RETRY_DELAY,
+                            _ => RETRY_DELAY,
+                        };
+                        updates_sender.send(uuid, Event::Error(error));
 
                         // Sleep before trying to connect again
-                        sleep(Duration::from_secs(1)).await;
+                        sleep(retry_delay).await;
                     }
                 }
             }
 
-            updates_sender.send((uuid, Event::Disconnected)).await.ok();
+            updates_sender.send(uuid, Event::Disconnected);
+            updates_sender.close(uuid);
         });
 
         self.active_connections
@@ -145,24 +299,82 @@ impl ConnectionManager {
         return Ok(connection);
     }
 
+    /// Returns how the events of every connected application are ingested
+    pub(crate) fn ingestion_stats(&self) -> HashMap<Uuid, IngestionStats> {
+        self.updates_sender.stats()
+    }
+
     pub(crate) async fn disconnect_app(&self, uuid: Uuid) {
         self.active_connections.write().await.remove(&uuid);
     }
 
-    async fn connect_to_app(url: &Url) -> Result<Box<Streaming<Update>>, TraceError> {
-        let endpoint = Endpoint::new(url.to_string()).map_err(|e| TraceError::Anyhow(e.into()))?;
+    /// Forwards the details of a task until the application stops sending them,
+    /// returns the id of the task
+    async fn watch_task_details(
+        uuid: Uuid,
+        task_id: u64,
+        mut client: Client,
+        updates_sender: EventSender,
+    ) -> u64 {
+        let request = tonic::Request::new(TaskDetailsRequest {
+            id: Some(task_id.into()),
+        });
+        match client.watch_task_details(request).await {
+            Ok(response) => {
+                let mut details_stream = response.into_inner();
+                while let Ok(Some(details)) = details_stream.message().await {
+                    updates_sender.send(uuid, Event::TaskDetails(details));
+                }
+            }
+            Err(error) => {
+                warn!("Could not watch the details of task {task_id} due to {error:?}");
+            }
+        }
+
+        task_id
+    }
+
+    /// Runs the pre-connect command, if any and not already running, then
+    /// connects to the application
+    async fn prepare_and_connect(
+        url: &Url,
+        pre_connect: Option<&PreConnectHook>,
+        hook: &mut Option<HookProcess>,
+        metadata: &MetadataInterceptor,
+    ) -> Result<(Client, Box<Streaming<Update>>), TraceError> {
+        if let Some(pre_connect) = pre_connect {
+            if !hook.as_mut().is_some_and(HookProcess::is_running) {
+                // The exited command is released before running it again
+                *hook = None;
+                *hook = Some(pre_connect::start(pre_connect, url).await?);
+            }
+        }
+        Self::connect_to_app(url, metadata.clone()).await
+    }
+
+    /// Resolves with the failure of the pre-connect command, never resolves without one
+    async fn hook_failure(hook: &mut Option<HookProcess>) -> TraceError {
+        match hook {
+            Some(hook) => hook.failure().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    async fn connect_to_app(
+        url: &Url,
+        metadata: MetadataInterceptor,
+    ) -> Result<(Client, Box<Streaming<Update>>), TraceError> {
+        let endpoint = Endpoint::new(url.to_string()).map_err(|e| TraceError::InvalidEndpoint {
+            url: url.to_string(),
+            error: e.to_string(),
+        })?;
         let channel = endpoint
             .connect()
             .await
-            .map_err(|e| TraceError::Anyhow(e.into()))?;
-        let mut client = InstrumentClient::new(channel);
+            .map_err(|e| TraceError::connection(url.as_str(), &e))?;
+        let mut client = InstrumentClient::with_interceptor(channel, metadata);
         let update_request = tonic::Request::new(InstrumentRequest {});
-        Ok(Box::new(
-            client
-                .watch_updates(update_request)
-                .await
-                .map_err(|e| TraceError::Anyhow(e.into()))?
-                .into_inner(),
-        ))
+        let update_stream = Box::new(client.watch_updates(update_request).await?.into_inner());
+        Ok((client, update_stream))
     }
 }
@@ -1,25 +1,34 @@
 use crate::{
-    domain::{application::Application, storable::Storable, Task},
+    domain::{
+        application::Application, secrets::Secrets, settings::Settings, storable::Storable, Task,
+    },
     error::Error as TraceError,
-    infra::{guard::WriteableDataBaseGuard, storage::Storage},
+    infra::{
+        guard::WriteableDataBaseGuard,
+        snapshot::Snapshot,
+        storage::{Applications, Storage, Tasks},
+    },
 };
 use async_trait::async_trait;
 use log::{debug, error};
-use std::{collections::HashMap, sync::Arc};
-use tokio::sync::RwLock;
-use uuid::Uuid;
+use std::{sync::Arc, time::Duration};
+
+/// Delay between two writes of the tasks file, which can be large
+const TASKS_WRITE_DELAY: Duration = Duration::from_secs(5);
 
 /// Representation of all data stored on the disk for persistency
 /// Provides read/write mechanisms that assure syncronisation with
 /// disk files
 #[derive(Default)]
 pub(crate) struct Database {
-    storage_folder: String,
-
+    // Data is kept in memory only if it was not created with a folder
     // todo: astea trebuie scrise pe disk + incarcate la pornire
-    applications: tokio::sync::RwLock<HashMap<Uuid, Arc<Application>>>,
+    applications: Snapshot<Applications>,
     // toate taskurile curente de la toate aplicatiile
-    tasks: tokio::sync::RwLock<HashMap<String, Arc<Task>>>,
+    tasks: Snapshot<Tasks>,
+    settings: Snapshot<Settings>,
+    // Kept apart from the applications, in a file only readable by the user
+    secrets: Snapshot<Secrets>,
 }
 
 impl Database {
@@ -32,10 +41,37 @@ impl Database {
     ///
     /// This method should be used if loading failed
     pub(crate) fn new(storage_folder: String) -> Self {
+        Self::stored(
+            &storage_folder,
+            Applications::new(),
+            Tasks::new(),
+            Settings::default(),
+            Secrets::default(),
+        )
+    }
+
+    /// Is creating a fresh database instance which is never written to the disk
+    pub(crate) fn in_memory() -> Self {
+        Self::default()
+    }
+
+    fn stored(
+        storage_folder: &str,
+        applications: Applications,
+        tasks: Tasks,
+        settings: Settings,
+        secrets: Secrets,
+    ) -> Self {
         Self {
-            storage_folder,
-            applications: RwLock::new(HashMap::new()),
-            tasks: RwLock::new(HashMap::new()),
+            applications: Snapshot::stored(applications, storage_folder, "applications", false),
+            tasks: Snapshot::stored_in_background(
+                tasks,
+                storage_folder,
+                "tasks",
+                TASKS_WRITE_DELAY,
+            ),
+            settings: Snapshot::stored(settings, storage_folder, "settings", false),
+            secrets: Snapshot::stored(secrets, storage_folder, "secrets", true),
         }
     }
 
@@ -49,30 +85,29 @@ impl Database {
     /// an error will be returned
     pub(crate) async fn load(storage_folder: String) -> Result<Self, TraceError> {
         // Load all applications
-        let applications: HashMap<Uuid, Arc<Application>> =
-            match Application::load_all(storage_folder.clone()).await {
-                Ok(apps) => apps
-                    .into_iter()
-                    .map(|(id, app)| (id, Arc::new(app)))
-                    .collect(),
-                Err(error) => match error {
-                    TraceError::PathNotFound(_) => {
-                        debug!("Applications file not found, using empty list");
-                        HashMap::new()
-                    }
-                    _ => {
-                        error!("Failed to load applications due to {error:?}");
-                        return Err(error);
-                    }
-                },
-            };
+        let applications: Applications = match Application::load_all(storage_folder.clone()).await {
+            Ok(apps) => apps
+                .into_iter()
+                .map(|(id, app)| (id, Arc::new(app)))
+                .collect(),
+            Err(error) => match error {
+                TraceError::PathNotFound(_) => {
+                    debug!("Applications file not found, using empty list");
+                    Applications::new()
+                }
+                _ => {
+                    error!("Failed to load applications due to {error:?}");
+                    return Err(error);
+                }
+            },
+        };
         debug!(
             "Successfully loaded {} applications from disk.",
             applications.values().len()
         );
 
         // Load all tasks
-        let tasks: HashMap<String, Arc<Task>> = match Task::load_all(storage_folder.clone()).await {
+        let tasks: Tasks = match Task::load_all(storage_folder.clone()).await {
             Ok(tasks) => tasks
                 .into_iter()
                 .map(|(id, task)| (id, Arc::new(task)))
@@ -80,7 +115,7 @@ impl Database {
             Err(error) => match error {
                 TraceError::PathNotFound(_) => {
                     debug!("Tasks file not found, using empty list");
-                    HashMap::new()
+                    Tasks::new()
                 }
                 _ => {
                     error!("Failed to load applications due to {error:?}");
@@ -93,43 +128,78 @@ impl Database {
             tasks.values().len()
         );
 
-        Ok(Self {
-            storage_folder,
-            applications: RwLock::new(applications),
-            tasks: RwLock::new(tasks),
-        })
+        // Load settings
+        let settings = match Settings::load_all(storage_folder.clone()).await {
+            Ok(settings) => settings,
+            Err(TraceError::PathNotFound(_)) => {
+                debug!("Settings file not found, using default settings");
+                Settings::default()
+            }
+            Err(error) => {
+                error!("Failed to load settings due to {error:?}");
+                return Err(error);
+            }
+        };
+
+        // Load secrets
+        let secrets = match Secrets::load_all(storage_folder.clone()).await {
+            Ok(secrets) => secrets,
+            Err(TraceError::PathNotFound(_)) => {
+                debug!("Secrets file not found, using no secrets");
+                Secrets::default()
+            }
+            Err(error) => {
+                error!("Failed to load secrets due to {error:?}");
+                return Err(error);
+            }
+        };
+
+        Ok(Self::stored(
+            &storage_folder,
+            applications,
+            tasks,
+            settings,
+            secrets,
+        ))
+    }
+
+    /// Returns the tasks stored when the database was created
+    pub(crate) fn loaded_tasks(&self) -> Arc<Tasks> {
+        self.tasks.read()
     }
 }
 
 #[async_trait]
 impl Storage for Database {
-    async fn applications_read(&self) -> HashMap<Uuid, Arc<Application>> {
-        self.applications.read().await.clone()
+    async fn applications_read(&self) -> Applications {
+        Applications::clone(&self.applications.read())
     }
 
-    async fn applications_write(
-        &self,
-    ) -> WriteableDataBaseGuard<'_, HashMap<Uuid, Arc<Application>>> {
-        let elements = self.applications.write().await;
+    async fn applications_write(&self) -> WriteableDataBaseGuard<'_, Applications> {
+        self.applications.write().await
+    }
 
-        WriteableDataBaseGuard {
-            folder: &self.storage_folder,
-            title: "applications",
-            elements,
-        }
+    async fn tasks_read(&self) -> Tasks {
+        Tasks::clone(&self.tasks.read())
     }
 
-    async fn tasks_read(&self) -> HashMap<String, Arc<Task>> {
-        self.tasks.read().await.clone()
+    async fn tasks_write(&self) -> WriteableDataBaseGuard<'_, Tasks> {
+        self.tasks.write().await
     }
 
-    async fn tasks_write(&self) -> WriteableDataBaseGuard<'_, HashMap<String, Arc<Task>>> {
-        let elements = self.tasks.write().await;
+    async fn settings_read(&self) -> Settings {
+        Settings::clone(&self.settings.read())
+    }
 
-        WriteableDataBaseGuard {
-            folder: &self.storage_folder,
-            title: "tasks",
-            elements,
-        }
+    async fn settings_write(&self) -> WriteableDataBaseGuard<'_, Settings> {
+        self.settings.write().await
+    }
+
+    async fn secrets_read(&self) -> Secrets {
+        Secrets::clone(&self.secrets.read())
+    }
+
+    async fn secrets_write(&self) -> WriteableDataBaseGuard<'_, Secrets> {
+        self.secrets.write().await
     }
 }
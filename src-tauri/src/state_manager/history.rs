@@ -0,0 +1,524 @@
+use crate::domain::Task;
+use crate::infra::storage::Tasks;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    ops::{AddAssign, Div, Mul},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use uuid::Uuid;
+
+/// Resolution and retention of every level of the series of an application
+///
+/// The first level keeps one sample per second, older samples are averaged
+/// into the coarser levels, and dropped once they exceed the last retention.
+const TIERS: &[(Duration, Duration)] = &[
+    (Duration::from_secs(1), Duration::from_secs(10 * 60)),
+    (Duration::from_secs(10), Duration::from_secs(2 * 60 * 60)),
+    (Duration::from_secs(60), Duration::from_secs(24 * 60 * 60)),
+];
+
+/// Levels of the series of a task, coarser and shorter than the ones of an
+/// application: about 300 buckets of 64 bytes, ie: 20 KB per task
+const TASK_TIERS: &[(Duration, Duration)] = &[
+    (Duration::from_secs(10), Duration::from_secs(30 * 60)),
+    (Duration::from_secs(60), Duration::from_secs(2 * 60 * 60)),
+];
+
+/// Maximum number of tasks that have their own series at the same time
+const MAX_TASK_SERIES: usize = 1_000;
+
+#[derive(Serialize, Clone, Copy, Debug, Default)]
+pub struct Metrics {
+    pub live_tasks: f64,
+    pub polls_per_sec: f64,
+    /// Time spent polling divided by the elapsed time, for an application
+    /// this is the number of workers kept busy and may exceed 1
+    pub busy_ratio: f64,
+    pub wakes_per_sec: f64,
+    /// Time spent waiting to be polled once woken divided by the elapsed time,
+    /// for an application this is the number of tasks kept waiting
+    pub scheduled_ratio: f64,
+    /// Mean delay between a wake and the poll, in seconds
+    pub scheduling_latency: f64,
+}
+
+impl AddAssign for Metrics {
+    fn add_assign(&mut self, other: Self) {
+        self.live_tasks += other.live_tasks;
+        self.polls_per_sec += other.polls_per_sec;
+        self.busy_ratio += other.busy_ratio;
+        self.wakes_per_sec += other.wakes_per_sec;
+        self.scheduled_ratio += other.scheduled_ratio;
+        self.scheduling_latency += other.scheduling_latency;
+    }
+}
+
+impl Mul<f64> for Metrics {
+    type Output = Metrics;
+
+    fn mul(self, weight: f64) -> Self::Output {
+        Metrics {
+            live_tasks: self.live_tasks * weight,
+            polls_per_sec: self.polls_per_sec * weight,
+            busy_ratio: self.busy_ratio * weight,
+            wakes_per_sec: self.wakes_per_sec * weight,
+            scheduled_ratio: self.scheduled_ratio * weight,
+            scheduling_latency: self.scheduling_latency * weight,
+        }
+    }
+}
+
+impl Div<f64> for Metrics {
+    type Output = Metrics;
+
+    fn div(self, count: f64) -> Self::Output {
+        Metrics {
+            live_tasks: self.live_tasks / count,
+            polls_per_sec: self.polls_per_sec / count,
+            busy_ratio: self.busy_ratio / count,
+            wakes_per_sec: self.wakes_per_sec / count,
+            scheduled_ratio: self.scheduled_ratio / count,
+            scheduling_latency: self.scheduling_latency / count,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MetricsSample {
+    /// Milliseconds since UNIX epoch, in the local clock (the clock offset of
+    /// the application is already applied)
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub metrics: Metrics,
+}
+
+/// Rates of a task over the latest seconds, to rank the tasks by their
+/// current activity rather than by their cumulative counters
+#[derive(Serialize, Clone, Debug)]
+pub struct TaskActivity {
+    pub task: Arc<Task>,
+    #[serde(flatten)]
+    pub rates: Metrics,
+}
+
+/// Rate the tasks are ranked by
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityOrder {
+    #[default]
+    Busy,
+    Polls,
+    Wakes,
+}
+
+impl ActivityOrder {
+    pub fn rate(&self, metrics: &Metrics) -> f64 {
+        match self {
+            ActivityOrder::Busy => metrics.busy_ratio,
+            ActivityOrder::Polls => metrics.polls_per_sec,
+            ActivityOrder::Wakes => metrics.wakes_per_sec,
+        }
+    }
+}
+
+/// Samples falling in the same time slot of a level, averaged when read
+///
+/// Every sample is weighted by the seconds it covers, so that the mean of
+/// the rates is the rate over the whole time, whatever the update intervals
+#[derive(Debug)]
+struct Bucket {
+    timestamp: u64,
+    /// Sum of the samples multiplied by their weight
+    sum: Metrics,
+    /// Seconds covered by the samples
+    weight: f64,
+}
+
+impl Bucket {
+    fn sample(&self) -> MetricsSample {
+        MetricsSample {
+            timestamp: self.timestamp,
+            metrics: self.sum / self.weight,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Tier {
+    resolution: u64,
+    retention: u64,
+    buckets: VecDeque<Bucket>,
+}
+
+impl Tier {
+    fn add(&mut self, timestamp: u64, sum: Metrics, weight: f64) {
+        let timestamp = timestamp - timestamp % self.resolution;
+        match self.buckets.back_mut() {
+            Some(bucket) if bucket.timestamp == timestamp => {
+                bucket.sum += sum;
+                bucket.weight += weight;
+            }
+            _ => self.buckets.push_back(Bucket {
+                timestamp,
+                sum,
+                weight,
+            }),
+        }
+    }
+}
+
+/// Time-series downsampled as it ages
+#[derive(Debug)]
+struct Series {
+    tiers: Vec<Tier>,
+}
+
+impl Series {
+    fn new(tiers: &[(Duration, Duration)]) -> Self {
+        Self {
+            tiers: tiers
+                .iter()
+                .map(|(resolution, retention)| Tier {
+                    resolution: resolution.as_millis() as u64,
+                    retention: retention.as_millis() as u64,
+                    buckets: VecDeque::new(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Adds the rates measured over the `elapsed` seconds ending at `timestamp`
+    fn push(&mut self, timestamp: u64, metrics: Metrics, elapsed: f64) {
+        self.tiers[0].add(timestamp, metrics * elapsed, elapsed);
+
+        // Move the samples that are too old for a level into the next one
+        for index in 0..self.tiers.len() {
+            let limit = timestamp.saturating_sub(self.tiers[index].retention);
+            while let Some(bucket) = self.tiers[index].buckets.front() {
+                if bucket.timestamp >= limit {
+                    break;
+                }
+                let bucket = self.tiers[index].buckets.pop_front().unwrap();
+                if let Some(next) = self.tiers.get_mut(index + 1) {
+                    next.add(bucket.timestamp, bucket.sum, bucket.weight);
+                }
+            }
+        }
+    }
+
+    /// Mean of the samples recorded since `from` weighted by the time they
+    /// cover, none if there are none
+    ///
+    /// Buckets ending after `from` are included, so that coarse levels still
+    /// answer for windows shorter than their resolution
+    fn mean_since(&self, from: u64) -> Option<Metrics> {
+        let mut sum = Metrics::default();
+        let mut weight = 0.0;
+        for bucket in self.tiers.iter().flat_map(|tier| {
+            tier.buckets
+                .iter()
+                .filter(move |bucket| bucket.timestamp + tier.resolution > from)
+        }) {
+            sum += bucket.sum;
+            weight += bucket.weight;
+        }
+
+        (weight > 0.0).then(|| sum / weight)
+    }
+
+    fn last_timestamp(&self) -> Option<u64> {
+        self.tiers
+            .iter()
+            .find_map(|tier| tier.buckets.back())
+            .map(|bucket| bucket.timestamp)
+    }
+
+    /// Returns the samples in chronological order, coarser levels
+    /// only hold data older than the finer ones
+    fn query(&self, from: Option<u64>, to: Option<u64>) -> Vec<MetricsSample> {
+        self.tiers
+            .iter()
+            .rev()
+            .flat_map(|tier| tier.buckets.iter())
+            .filter(|bucket| from.is_none_or(|from| bucket.timestamp >= from))
+            .filter(|bucket| to.is_none_or(|to| bucket.timestamp <= to))
+            .map(Bucket::sample)
+            .collect()
+    }
+}
+
+/// Cumulative counters of a task at the time of the previous sample
+#[derive(Clone, Copy, Debug)]
+struct Counters {
+    /// Time of the update, in the application's clock
+    at: SystemTime,
+    polls: u64,
+    busy_time: Duration,
+    scheduled_time: Duration,
+    wakes: u64,
+}
+
+impl Counters {
+    fn of(task: &Task, at: SystemTime) -> Self {
+        Self {
+            at,
+            polls: task.stats.polls,
+            busy_time: task.stats.busy_time,
+            scheduled_time: task.stats.scheduled_time,
+            wakes: task.stats.wakes,
+        }
+    }
+
+    /// Changes since the `previous` counters, zero if they were reset
+    fn since(&self, previous: &Counters) -> Counters {
+        Counters {
+            at: self.at,
+            polls: self.polls.saturating_sub(previous.polls),
+            busy_time: self.busy_time.saturating_sub(previous.busy_time),
+            scheduled_time: self.scheduled_time.saturating_sub(previous.scheduled_time),
+            wakes: self.wakes.saturating_sub(previous.wakes),
+        }
+    }
+
+    fn add(&mut self, other: &Counters) {
+        self.polls += other.polls;
+        self.busy_time += other.busy_time;
+        self.scheduled_time += other.scheduled_time;
+        self.wakes += other.wakes;
+    }
+
+    /// Rates of the changes over `elapsed` seconds
+    fn rates(&self, live_tasks: f64, elapsed: f64) -> Metrics {
+        Metrics {
+            live_tasks,
+            polls_per_sec: self.polls as f64 / elapsed,
+            busy_ratio: self.busy_time.as_secs_f64() / elapsed,
+            wakes_per_sec: self.wakes as f64 / elapsed,
+            scheduled_ratio: self.scheduled_time.as_secs_f64() / elapsed,
+            scheduling_latency: mean_latency(self.scheduled_time, self.polls),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TaskSeries {
+    series: Series,
+    /// The task completed, its series is kept until room is needed
+    ended: bool,
+}
+
+#[derive(Debug)]
+struct ApplicationHistory {
+    series: Series,
+    /// Time of the previous update, in the application's clock
+    last_update: Option<SystemTime>,
+    /// Counters of the live tasks at the previous update they changed in
+    live: HashMap<String, Counters>,
+}
+
+/// Bounded store of the metrics history of every application and task,
+/// recorded at each update received from the applications
+#[derive(Debug, Default)]
+pub(crate) struct MetricsHistory {
+    applications: HashMap<Uuid, ApplicationHistory>,
+    tasks: HashMap<String, TaskSeries>,
+    // Series of the completed tasks, the first to be removed when full
+    ended: VecDeque<String>,
+}
+
+impl MetricsHistory {
+    /// Records a sample for the application and for the tasks of the update,
+    /// `task_ids`, the completed tasks are only used to stop their series
+    ///
+    /// Applications only send the stats of the tasks which changed, the other
+    /// tasks are not walked. Rates are computed from the counters of the
+    /// previous update, so tasks are sampled starting with the second update
+    /// they are part of. The time of the update is `now` in the application's
+    /// clock and `at` in the local one.
+    pub fn record(
+        &mut self,
+        app_id: Uuid,
+        now: SystemTime,
+        at: SystemTime,
+        tasks: &Tasks,
+        task_ids: &[u64],
+    ) {
+        let timestamp = to_millis(at);
+        let application = self
+            .applications
+            .entry(app_id)
+            .or_insert_with(|| ApplicationHistory {
+                series: Series::new(TIERS),
+                last_update: None,
+                live: HashMap::new(),
+            });
+        // Changes of all the tasks since the previous update
+        let mut changes = Counters {
+            at: now,
+            polls: 0,
+            busy_time: Duration::ZERO,
+            scheduled_time: Duration::ZERO,
+            wakes: 0,
+        };
+
+        for task_id in task_ids {
+            let key = format!("{app_id}.{task_id}");
+            let Some(task) = tasks.get(&key) else {
+                application.live.remove(&key);
+                continue;
+            };
+            let counters = Counters::of(task, now);
+            let previous = if task.stats.dropped_at.is_some() {
+                application.live.remove(&key)
+            } else {
+                application.live.insert(key.clone(), counters)
+            };
+
+            if let Some(previous) = previous {
+                let task_changes = counters.since(&previous);
+                changes.add(&task_changes);
+
+                let elapsed = now
+                    .duration_since(previous.at)
+                    .unwrap_or_default()
+                    .as_secs_f64();
+                if elapsed > 0.0 {
+                    if !self.tasks.contains_key(&key) {
+                        // Live tasks take the place of the completed ones
+                        while self.tasks.len() >= MAX_TASK_SERIES {
+                            let Some(ended) = self.ended.pop_front() else {
+                                break;
+                            };
+                            self.tasks.remove(&ended);
+                        }
+                    }
+                    if self.tasks.len() < MAX_TASK_SERIES || self.tasks.contains_key(&key) {
+                        self.tasks
+                            .entry(key.clone())
+                            .or_insert_with(|| TaskSeries {
+                                series: Series::new(TASK_TIERS),
+                                ended: false,
+                            })
+                            .series
+                            .push(timestamp, task_changes.rates(1.0, elapsed), elapsed);
+                    }
+                }
+            }
+
+            if task.stats.dropped_at.is_some() {
+                if let Some(task_series) = self.tasks.get_mut(&key) {
+                    if !task_series.ended {
+                        task_series.ended = true;
+                        self.ended.push_back(key);
+                    }
+                }
+            }
+        }
+
+        let elapsed = application
+            .last_update
+            .and_then(|last_update| now.duration_since(last_update).ok())
+            .unwrap_or_default()
+            .as_secs_f64();
+        application.last_update = Some(now);
+        if elapsed > 0.0 {
+            let live_tasks = application.live.len() as f64;
+            application
+                .series
+                .push(timestamp, changes.rates(live_tasks, elapsed), elapsed);
+        }
+
+        // Forget the tasks which have nothing left in their history
+        let max_retention = TASK_TIERS[TASK_TIERS.len() - 1].1.as_millis() as u64;
+        let limit = timestamp.saturating_sub(max_retention);
+        self.tasks.retain(|_, task_series| {
+            task_series
+                .series
+                .last_timestamp()
+                .is_some_and(|last| last >= limit)
+        });
+        let tasks = &self.tasks;
+        self.ended.retain(|key| tasks.contains_key(key));
+    }
+
+    /// Forgets the live tasks of an application whose process restarted,
+    /// the new process sends all its tasks again
+    pub fn restarted(&mut self, app_id: Uuid) {
+        if let Some(application) = self.applications.get_mut(&app_id) {
+            application.live.clear();
+        }
+    }
+
+    /// Returns the history of an application, or of one of its tasks if
+    /// `task_id` is set, between the `from` and `to` timestamps
+    /// (milliseconds since UNIX epoch)
+    pub fn query(
+        &self,
+        app_id: Uuid,
+        task_id: Option<u64>,
+        from: Option<u64>,
+        to: Option<u64>,
+    ) -> Vec<MetricsSample> {
+        let series = match task_id {
+            Some(task_id) => self
+                .tasks
+                .get(&format!("{}.{}", app_id, task_id))
+                .map(|task_series| &task_series.series),
+            None => self
+                .applications
+                .get(&app_id)
+                .map(|application| &application.series),
+        };
+
+        series
+            .map(|series| series.query(from, to))
+            .unwrap_or_default()
+    }
+
+    /// Returns the mean rates of an application, or of one of its tasks if
+    /// `task_id` is set, since the `from` timestamp
+    pub fn window(&self, app_id: Uuid, task_id: Option<u64>, from: u64) -> Option<Metrics> {
+        match task_id {
+            Some(task_id) => self
+                .tasks
+                .get(&format!("{}.{}", app_id, task_id))
+                .and_then(|task_series| task_series.series.mean_since(from)),
+            None => self.applications.get(&app_id)?.series.mean_since(from),
+        }
+    }
+
+    /// Returns the mean rates of every task sampled since the `from`
+    /// timestamp, by task key
+    pub fn task_windows(&self, from: u64) -> HashMap<String, Metrics> {
+        self.tasks
+            .iter()
+            .filter_map(|(key, task_series)| {
+                Some((key.clone(), task_series.series.mean_since(from)?))
+            })
+            .collect()
+    }
+
+    pub fn remove_app(&mut self, app_id: Uuid) {
+        let prefix = format!("{}.", app_id);
+        self.applications.remove(&app_id);
+        self.tasks.retain(|key, _| !key.starts_with(&prefix));
+        self.ended.retain(|key| !key.starts_with(&prefix));
+    }
+}
+
+/// Mean scheduled time of the polls, in seconds
+fn mean_latency(scheduled_time: Duration, polls: u64) -> f64 {
+    if polls == 0 {
+        0.0
+    } else {
+        scheduled_time.as_secs_f64() / polls as f64
+    }
+}
+
+fn to_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,46 @@
+//! Custom gRPC metadata (eg: `authorization`) sent with every request to an application,
+//! for the applications behind authenticating proxies
+
+use crate::error::Error as TraceError;
+use std::collections::HashMap;
+use tonic::{
+    metadata::{AsciiMetadataKey, AsciiMetadataValue},
+    service::Interceptor,
+    Request, Status,
+};
+
+#[derive(Clone, Default)]
+pub(crate) struct MetadataInterceptor {
+    metadata: Vec<(AsciiMetadataKey, AsciiMetadataValue)>,
+}
+
+impl MetadataInterceptor {
+    /// # Error
+    ///
+    /// If a name or a value is not valid gRPC metadata, an error naming
+    /// the entry will be returned (values are never part of the error)
+    pub fn new(metadata: &HashMap<String, String>) -> Result<Self, TraceError> {
+        let metadata = metadata
+            .iter()
+            .map(|(name, value)| {
+                let key = AsciiMetadataKey::from_bytes(name.as_bytes())
+                    .map_err(|_| TraceError::InvalidMetadata(format!("name {name}")))?;
+                let mut value = AsciiMetadataValue::try_from(value.as_str())
+                    .map_err(|_| TraceError::InvalidMetadata(format!("value of {name}")))?;
+                value.set_sensitive(true);
+                Ok((key, value))
+            })
+            .collect::<Result<_, TraceError>>()?;
+
+        Ok(Self { metadata })
+    }
+}
+
+impl Interceptor for MetadataInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        for (key, value) in &self.metadata {
+            request.metadata_mut().insert(key.clone(), value.clone());
+        }
+        Ok(request)
+    }
+}
@@ -1,20 +1,107 @@
 // TODO: check if pub needed
+mod clock;
 pub mod connection_manager;
 mod database;
+pub mod history;
+mod metadata;
+pub(crate) mod pipeline;
+mod pre_connect;
 pub mod state;
 
-use crate::domain::application::Application;
-use crate::error::Error as TraceError;
+use crate::analyzers::blocking::{BlockingDetector, BlockingIncident, BlockingReport};
+use crate::analyzers::deadlock::{DeadlockDetector, DeadlockReport};
+use crate::analyzers::grouping::{TaskGroup, TaskGrouper, TaskGrouping};
+use crate::analyzers::leak::{LeakDetector, LeakReport};
+use crate::analyzers::scheduling::{self, SchedulingReport, SATURATION_WINDOW};
+use crate::collector::{client::CollectorClient, Request};
+use crate::discovery::{discover, DiscoveredEndpoint};
+use crate::domain::application::{
+    Application, ApplicationState, ConnectionStatus, OutputLine, OutputStream, PreConnectHook,
+};
+use crate::domain::data_loss::{DataLoss, DroppedEvents};
+use crate::domain::secrets::Metadata;
+use crate::domain::settings::{ApiSettings, MetricsEndpointSettings, Settings};
+use crate::domain::{Task, TaskHistograms};
+use crate::endpoints::{api, prometheus, LocalServer};
+use crate::error::{Error as TraceError, ErrorReport};
+use crate::export::{export, ExportFormat, Snapshot};
+use crate::import::read_recording;
+use crate::mappers::map_timestamp;
+use crate::proxy::{start_proxy, ProxyHub};
+use crate::query::{query_tasks, TaskPage, TaskQuery};
+use crate::state_manager::history::{ActivityOrder, Metrics, MetricsSample, TaskActivity};
 use crate::state_manager::state::State;
 use anyhow::Result;
-use connection_manager::{ConnectionManager, Event};
-use log::{error, info};
-use std::sync::Arc;
-use tauri::{AppHandle, Emitter as _};
-use tokio::sync::mpsc::{self, Receiver};
+use connection_manager::{Command, Connection, ConnectionManager, Event};
+use console_api::instrument::Update;
+use log::{error, info, warn};
+use metadata::MetadataInterceptor;
+pub(crate) use pipeline::IngestionStats;
+use pipeline::{pipeline, EventReceiver};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    net::{Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tauri::{async_runtime, AppHandle, Emitter as _};
+use tauri_plugin_shell::{
+    process::{CommandChild, CommandEvent},
+    ShellExt,
+};
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, Mutex};
 use url::Url;
 use uuid::Uuid;
 
+/// Number of live updates kept for the slowest subscriber
+const LIVE_UPDATES_CAPACITY: usize = 16;
+
+/// Variable read by console-subscriber for the address to listen on
+const CONSOLE_BIND_VARIABLE: &str = "TOKIO_CONSOLE_BIND";
+
+/// Period the windowed rates are computed over, if not given
+const DEFAULT_RATE_WINDOW: Duration = Duration::from_secs(10);
+
+/// Tasks returned by the most active tasks, if not given
+const DEFAULT_TOP_TASKS: usize = 20;
+
+/// Delay between two compactions of the stored tasks
+const COMPACTION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Tasks of a location whose details are watched for its histograms
+const MAX_LOCATION_WATCHED_TASKS: usize = 32;
+
+/// Line written by a launched application, sent to the user interface
+#[derive(Serialize, Clone)]
+struct OutputEvent<'a> {
+    app_id: Uuid,
+    #[serde(flatten)]
+    line: &'a OutputLine,
+}
+
+/// Event sent to the user interface, also published to the API clients
+#[derive(Serialize, Clone)]
+#[serde(tag = "event", content = "payload")]
+pub enum LiveUpdate {
+    #[serde(rename = "update:tasks")]
+    Tasks(Vec<Arc<Task>>),
+    #[serde(rename = "update:applications")]
+    Applications(Vec<Arc<Application>>),
+    #[serde(rename = "warning:deadlock")]
+    Deadlocks(Vec<DeadlockReport>),
+    #[serde(rename = "warning:data_loss")]
+    DataLoss(HashMap<Uuid, DataLoss>),
+    #[serde(rename = "warning:blocking")]
+    Blocking(Vec<BlockingIncident>),
+    #[serde(rename = "warning:task_leak")]
+    TaskLeaks(Vec<LeakReport>),
+    #[serde(rename = "update:task_groups")]
+    TaskGroups(Vec<TaskGroup>),
+}
+
 pub struct StateManager {
     // Mpsc used to receive updates about connected applications
     // (eg. number of running tasks, time ran)
@@ -26,12 +113,46 @@ pub struct StateManager {
     pub connection_manager: ConnectionManager,
 
     pub state: State,
+
+    // Looks for tasks waiting on each other
+    deadlock_detector: DeadlockDetector,
+    // Looks for locations whose tasks pile up
+    leak_detector: LeakDetector,
+    // Looks for polls blocking their worker thread
+    blocking_detector: BlockingDetector,
+
+    // Aggregates the tasks, the watched grouping is sent periodically. The
+    // trends of the watched grouping are kept apart from the requested ones,
+    // which cover the time since the previous request.
+    task_grouper: TaskGrouper,
+    watched_grouper: TaskGrouper,
+    watched_grouping: Mutex<Option<TaskGrouping>>,
+
+    // Prometheus endpoint, running if enabled in the settings
+    metrics_endpoint: Mutex<Option<LocalServer>>,
+    // REST and WebSocket API, running if enabled in the settings
+    api_endpoint: Mutex<Option<LocalServer>>,
+    live_updates: broadcast::Sender<LiveUpdate>,
+
+    // Mirrors the updates of the applications for the proxies
+    pub(crate) proxy_hub: ProxyHub,
+    // Proxies re-exposing the applications to other consoles
+    proxies: Mutex<HashMap<Uuid, LocalServer>>,
+
+    // Set when attached to a collector, which then owns the
+    // connections and the storage
+    pub(crate) collector: Option<CollectorClient>,
+
+    // Processes of the applications launched by the user
+    launched: Mutex<HashMap<Uuid, CommandChild>>,
+
+    // Dropped events and completeness of every application as last emitted,
+    // the data loss is only emitted again once changed
+    emitted_data_loss: Mutex<HashMap<Uuid, (DroppedEvents, bool)>>,
 }
 
 impl StateManager {
-    pub async fn new() -> Result<(StateManager, Receiver<(Uuid, Event)>), TraceError> {
-        let (updates_sender, updates_receiver) = mpsc::channel(100);
-
+    pub async fn new() -> Result<(StateManager, EventReceiver), TraceError> {
         // TODO: check if error handling could be done better here (maybe looking for a single error is not the best case)
         let state = match State::load().await {
             // State loaded successfully
@@ -51,30 +172,72 @@ impl StateManager {
             }
         };
 
+        Ok(Self::with_state(state, None))
+    }
+
+    /// Creates a state manager attached to a collector, applications are
+    /// managed by the collector and nothing is stored by this instance
+    pub fn new_attached(collector: CollectorClient) -> (StateManager, EventReceiver) {
+        Self::with_state(State::in_memory(), Some(collector))
+    }
+
+    fn with_state(
+        state: State,
+        collector: Option<CollectorClient>,
+    ) -> (StateManager, EventReceiver) {
+        let (updates_sender, updates_receiver) = pipeline();
+
         let context = StateManager {
             connection_manager: ConnectionManager::new(updates_sender),
             state,
+            deadlock_detector: DeadlockDetector::default(),
+            leak_detector: LeakDetector::default(),
+            blocking_detector: BlockingDetector::default(),
+            task_grouper: TaskGrouper::default(),
+            watched_grouper: TaskGrouper::default(),
+            watched_grouping: Mutex::new(Some(TaskGrouping::default())),
+            metrics_endpoint: Mutex::new(None),
+            api_endpoint: Mutex::new(None),
+            live_updates: broadcast::channel(LIVE_UPDATES_CAPACITY).0,
+            proxy_hub: ProxyHub::default(),
+            proxies: Mutex::new(HashMap::new()),
+            collector,
+            launched: Mutex::new(HashMap::new()),
+            emitted_data_loss: Mutex::new(HashMap::new()),
         };
 
-        Ok((context, updates_receiver))
+        (context, updates_receiver)
     }
 
     // region events
 
-    pub async fn run(&self, mut updates_receiver: Receiver<(Uuid, Event)>) {
+    pub async fn run(self: &Arc<Self>, mut updates_receiver: EventReceiver) {
+        // Remove the completed tasks exceeding the retention limits, apart from
+        // the event loop as it goes through all the stored tasks
+        let state_manager = self.clone();
+        tokio::spawn(async move {
+            let mut compaction = tokio::time::interval(COMPACTION_INTERVAL);
+            loop {
+                compaction.tick().await;
+                let retention = state_manager.state.get_settings().await.retention;
+                let removed = state_manager.state.compact_tasks(&retention).await;
+                if removed > 0 {
+                    info!("Removed {removed} completed tasks exceeding the retention limits");
+                }
+            }
+        });
+
         // event loop
         loop {
             tokio::select! {
-                // Received updates about apps
-                Some((app_id, event)) = updates_receiver.recv() => {
-                    match event {
-                        Event::Update(update) => {
-                            if let Some(task_update) = update.task_update {
-                                self.state.handle_task_update(app_id, task_update).await;
-                            }
+                // Process the events of every app concurrently, as they are received
+                Some((app_id, events)) = updates_receiver.next_app() => {
+                    let state_manager = self.clone();
+                    tokio::spawn(async move {
+                        while let Some((event, received_at)) = events.recv().await {
+                            state_manager.handle_event(app_id, event, received_at).await;
                         }
-                        _ => {}
-                    }
+                    });
                 },
                 // todo: add other events receivers
                 // todo: add receiver to add application and send to connection manager then update state
@@ -82,6 +245,110 @@ impl StateManager {
         }
     }
 
+    /// Applies an event received from the connection to an application, at
+    /// the local time `received_at`
+    async fn handle_event(&self, app_id: Uuid, event: Event, received_at: SystemTime) {
+        match event {
+            Event::Update(update) => {
+                self.proxy_hub.publish_update(app_id, &update).await;
+                self.handle_update(app_id, update, received_at).await;
+            }
+            Event::TaskDetails(details) => {
+                self.proxy_hub.publish_task_details(app_id, &details).await;
+                self.state.handle_task_details(app_id, details).await;
+            }
+            Event::Connecting => {
+                self.state
+                    .set_connection_status(app_id, ConnectionStatus::Connecting)
+                    .await;
+                // The connection was lost or is not established yet
+                self.state.pause_clock(app_id).await;
+            }
+            Event::Connected => {
+                self.state
+                    .set_connection_status(app_id, ConnectionStatus::Connected)
+                    .await;
+                self.state.set_connection_error(app_id, None).await;
+                self.state.reset_dropped_events(app_id).await;
+            }
+            Event::Disconnected => {
+                self.state
+                    .set_connection_status(app_id, ConnectionStatus::Disconnected)
+                    .await;
+                self.state.pause_clock(app_id).await;
+            }
+            Event::Error(error) => {
+                self.state
+                    .set_connection_error(app_id, Some(error.report()))
+                    .await;
+            }
+        }
+    }
+
+    /// Applies an update received from an application at the local time
+    /// `received_at` to the state
+    pub(crate) async fn handle_update(
+        &self,
+        app_id: Uuid,
+        update: Update,
+        received_at: SystemTime,
+    ) {
+        let now = map_timestamp(update.now.as_ref());
+        if let Some(now) = now {
+            self.state.sync_clock(app_id, now, received_at).await;
+        }
+        let dropped_events = DroppedEvents {
+            tasks: update
+                .task_update
+                .as_ref()
+                .map_or(0, |update| update.dropped_events),
+            resources: update
+                .resource_update
+                .as_ref()
+                .map_or(0, |update| update.dropped_events),
+            async_ops: update
+                .async_op_update
+                .as_ref()
+                .map_or(0, |update| update.dropped_events),
+        };
+        self.state
+            .record_dropped_events(app_id, now.unwrap_or_else(SystemTime::now), dropped_events)
+            .await;
+        // Tasks created or changed by the update, the only ones sampled
+        let mut task_ids: Vec<u64> = update
+            .task_update
+            .as_ref()
+            .map(|task_update| {
+                task_update
+                    .new_tasks
+                    .iter()
+                    .filter_map(|task| task.id.map(|id| id.id))
+                    .chain(task_update.stats_update.keys().copied())
+                    .collect()
+            })
+            .unwrap_or_default();
+        task_ids.sort_unstable();
+        task_ids.dedup();
+        if let Some(task_update) = update.task_update {
+            self.state.handle_task_update(app_id, task_update).await;
+        }
+        // Async ops are handled before resources as the
+        // resource poll ops refer to them
+        if let Some(async_op_update) = update.async_op_update {
+            self.state
+                .handle_async_op_update(app_id, async_op_update)
+                .await;
+        }
+        if let Some(resource_update) = update.resource_update {
+            self.state
+                .handle_resource_update(app_id, resource_update, now)
+                .await;
+        }
+        if let Some(now) = now {
+            self.state.record_metrics(app_id, now, &task_ids).await;
+        }
+    }
+
     // endregion
 
     // region application
@@ -89,16 +356,34 @@ impl StateManager {
     /// Registers and enables a new application
     ///
     /// Is also connecting to the application in order to receive updates about it
-    pub async fn add_application(&self, title: String, url: Url) -> Result<Uuid, TraceError> {
+    pub async fn add_application(
+        &self,
+        title: String,
+        url: Url,
+        pre_connect: Option<PreConnectHook>,
+        metadata: HashMap<String, String>,
+    ) -> Result<Uuid, TraceError> {
+        if let Some(collector) = &self.collector {
+            return collector
+                .request(Request::AddApplication {
+                    title,
+                    url,
+                    pre_connect,
+                    metadata: Metadata(metadata),
+                })
+                .await;
+        }
+        MetadataInterceptor::new(&metadata)?;
+
         // Create and enable application
         let mut application = Application::new(title, url);
+        application.set_pre_connect(pre_connect);
         let app_id = application.id().clone();
 
-        // Connect to the app
-        let connection = self
-            .connection_manager
-            .connect_app(application.id().clone(), application.url().clone())
-            .await?;
+        // Metadata may hold credentials so it is kept in the secrets
+        self.state.store_app_metadata(app_id, metadata).await;
+
+        let connection = self.connect(&application).await?;
         application.enable(connection);
 
         // Store app
@@ -106,39 +391,857 @@ impl StateManager {
         Ok(app_id)
     }
 
+    /// Replaces the gRPC metadata sent to an application, reconnecting to it
+    /// if enabled so that the new metadata is used right away
+    pub async fn update_application_metadata(
+        &self,
+        uuid: Uuid,
+        metadata: HashMap<String, String>,
+    ) -> Result<(), TraceError> {
+        if let Some(collector) = &self.collector {
+            return collector
+                .request(Request::UpdateApplicationMetadata {
+                    app_id: uuid,
+                    metadata: Metadata(metadata),
+                })
+                .await;
+        }
+        MetadataInterceptor::new(&metadata)?;
+
+        let application = self
+            .state
+            .get_current_applications_list()
+            .await
+            .into_iter()
+            .find(|app| *app.id() == uuid)
+            .ok_or(TraceError::ApplicationNotFound(uuid))?;
+        self.state.store_app_metadata(uuid, metadata).await;
+
+        if application.state() == ApplicationState::Enabled {
+            info!("Reconnecting to application {uuid} with its new metadata");
+            self.state.disable_app(uuid).await?;
+            self.connection_manager.disconnect_app(uuid).await;
+            let connection = self.connect(&application).await?;
+            self.state.enable_app(uuid, connection).await?;
+        }
+        Ok(())
+    }
+
+    /// Connects to an application with the metadata stored in the secrets,
+    /// after running its pre-connect command
+    async fn connect(&self, application: &Application) -> Result<Connection, TraceError> {
+        let metadata = self.state.get_app_metadata(*application.id()).await;
+        let interceptor = MetadataInterceptor::new(&metadata)?;
+
+        self.connection_manager
+            .connect_app(
+                *application.id(),
+                application.url().clone(),
+                application.pre_connect().cloned(),
+                interceptor,
+            )
+            .await
+    }
+
+    /// Registers a read-only application out of a console-subscriber recording
+    pub async fn import_recording(&self, path: &Path) -> Result<Uuid, TraceError> {
+        let updates = read_recording(path).await?;
+
+        let title = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let url = Url::from_file_path(path)
+            .map_err(|_| TraceError::PathNotFound(path.to_string_lossy().to_string()))?;
+        let application = Application::new_offline(title, url);
+        let app_id = *application.id();
+        self.state.store_app(application).await;
+
+        info!(
+            "Replaying {} updates recorded in {path:?} for application {app_id}",
+            updates.len()
+        );
+        // Recordings keep their own clock, the local time is not used
+        for update in updates {
+            self.handle_update(app_id, update, SystemTime::now()).await;
+        }
+
+        Ok(app_id)
+    }
+
     pub async fn disable_application(&self, uuid: Uuid) -> Result<(), TraceError> {
+        if let Some(collector) = &self.collector {
+            return collector
+                .request(Request::DisableApplication { app_id: uuid })
+                .await;
+        }
+
         self.state.disable_app(uuid).await
     }
 
     /// Returns a list of the applications currently registered in the app
     /// (not necessarily active too)
-    pub async fn _current_applications(&self) -> Vec<Arc<Application>> {
+    pub async fn current_applications(&self) -> Vec<Arc<Application>> {
         self.state.get_current_applications_list().await
     }
 
+    /// Returns how the events of the connected applications are ingested
+    pub fn ingestion_stats(&self) -> HashMap<Uuid, IngestionStats> {
+        self.connection_manager.ingestion_stats()
+    }
+
+    /// Returns why the applications could not be connected to (eg: the error
+    /// output of a failed pre-connect command), until they get connected
+    pub async fn connection_errors(&self) -> HashMap<Uuid, ErrorReport> {
+        self.state.get_connection_errors().await
+    }
+
+    /// Returns the events the applications could not send, for the applications
+    /// which dropped any
+    pub async fn data_loss(&self) -> HashMap<Uuid, DataLoss> {
+        self.state.get_data_loss().await
+    }
+
+    /// Returns the metrics history of an application, or of one of its tasks
+    pub async fn metrics_history(
+        &self,
+        app_id: Uuid,
+        task_id: Option<u64>,
+        from: Option<u64>,
+        to: Option<u64>,
+    ) -> Vec<MetricsSample> {
+        self.state
+            .get_metrics_history(app_id, task_id, from, to)
+            .await
+    }
+
+    /// Returns the mean rates of an application, or of one of its tasks,
+    /// over the latest `window`
+    pub async fn windowed_rates(
+        &self,
+        app_id: Uuid,
+        task_id: Option<u64>,
+        window: Option<Duration>,
+    ) -> Option<Metrics> {
+        let from = window_start(window.unwrap_or(DEFAULT_RATE_WINDOW));
+        self.state.get_windowed_rates(app_id, task_id, from).await
+    }
+
+    /// Returns the live tasks the most active over the latest `window`, of
+    /// every application if `app_id` is not set
+    pub async fn top_tasks(
+        &self,
+        app_id: Option<Uuid>,
+        window: Option<Duration>,
+        order: ActivityOrder,
+        limit: Option<usize>,
+    ) -> Vec<TaskActivity> {
+        let from = window_start(window.unwrap_or(DEFAULT_RATE_WINDOW));
+        let rates = self.state.get_task_windows(from).await;
+
+        let mut activities: Vec<TaskActivity> = self
+            .state
+            .get_live_tasks()
+            .await
+            .into_iter()
+            .filter(|task| app_id.is_none_or(|app_id| task.app_id == app_id))
+            .filter_map(|task| {
+                let rates = *rates.get(&task.id())?;
+                Some(TaskActivity { task, rates })
+            })
+            .collect();
+        activities.sort_by(|left, right| {
+            order
+                .rate(&right.rates)
+                .total_cmp(&order.rate(&left.rates))
+                .then((left.task.app_id, left.task.id).cmp(&(right.task.app_id, right.task.id)))
+        });
+        activities.truncate(limit.unwrap_or(DEFAULT_TOP_TASKS));
+
+        activities
+    }
+
+    /// Returns the page of tasks matching the query
+    pub async fn query_tasks(&self, query: &TaskQuery) -> Result<TaskPage, TraceError> {
+        query_tasks(self.state.get_tasks().await, query)
+    }
+
+    /// Aggregates the tasks into groups, with the trends since the
+    /// grouping was last requested
+    pub async fn task_groups(&self, grouping: &TaskGrouping) -> Result<Vec<TaskGroup>, TraceError> {
+        self.group_tasks(&self.task_grouper, grouping).await
+    }
+
+    async fn group_tasks(
+        &self,
+        grouper: &TaskGrouper,
+        grouping: &TaskGrouping,
+    ) -> Result<Vec<TaskGroup>, TraceError> {
+        let tasks = self.state.get_tasks().await;
+        let mut now = HashMap::new();
+        for application in self.state.get_current_applications_list().await {
+            let app_id = *application.id();
+            if let Some(app_now) = self.state.get_app_now(app_id).await {
+                now.insert(app_id, app_now);
+            }
+        }
+        grouper.group(grouping, &tasks, &now)
+    }
+
+    /// Sets the grouping sent with the `update:task_groups` event, none to stop it
+    pub async fn watch_task_groups(
+        &self,
+        grouping: Option<TaskGrouping>,
+    ) -> Result<(), TraceError> {
+        if let Some(grouping) = &grouping {
+            TaskGrouper::validate(grouping)?;
+        }
+        *self.watched_grouping.lock().await = grouping;
+        Ok(())
+    }
+
+    /// Returns the suspected task leaks found by the latest analysis
+    pub fn task_leaks(&self, app_id: Option<Uuid>) -> Vec<LeakReport> {
+        let mut reports = self.leak_detector.latest();
+        if let Some(app_id) = app_id {
+            reports.retain(|report| report.app_id == app_id);
+        }
+        reports
+    }
+
+    /// Returns the polls of an application which exceeded the budget,
+    /// and the tasks blocking the longest
+    pub fn blocking_report(&self, app_id: Uuid) -> BlockingReport {
+        self.blocking_detector.report(app_id)
+    }
+
+    /// Returns the scheduling latencies of an application and of its live
+    /// tasks, with whether its runtime keeps up with the woken tasks
+    pub async fn scheduling_report(&self, app_id: Uuid) -> Result<SchedulingReport, TraceError> {
+        let applications = self.state.get_current_applications_list().await;
+        if !applications
+            .iter()
+            .any(|application| *application.id() == app_id)
+        {
+            return Err(TraceError::ApplicationNotFound(app_id));
+        }
+
+        let tasks = self.state.get_live_tasks().await;
+        let histograms = self.state.get_app_histograms(app_id).await;
+        let recent = self
+            .state
+            .get_metrics_history(app_id, None, Some(window_start(SATURATION_WINDOW)), None)
+            .await;
+
+        Ok(scheduling::analyze(app_id, &tasks, &histograms, &recent))
+    }
+
+    /// Returns the latest histograms of a task
+    ///
+    /// The details of the task are watched starting with the first call,
+    /// so they might not be available yet
+    pub async fn task_histograms(&self, app_id: Uuid, task_id: u64) -> Option<Arc<TaskHistograms>> {
+        self.watch_task_details(app_id, &[task_id]).await;
+        self.state.get_task_histograms(app_id, task_id).await
+    }
+
+    /// Returns the histograms of all the live tasks spawned at a location, merged
+    ///
+    /// Only the details of the newest `MAX_LOCATION_WATCHED_TASKS` tasks are
+    /// watched, the other tasks are merged if their details are known
+    pub async fn location_histograms(&self, app_id: Uuid, location: &str) -> TaskHistograms {
+        let mut task_ids: Vec<u64> = self
+            .state
+            .get_live_tasks()
+            .await
+            .iter()
+            .filter(|task| task.app_id == app_id && task.location.as_deref() == Some(location))
+            .map(|task| task.id)
+            .collect();
+        task_ids.sort_unstable_by(|left, right| right.cmp(left));
+
+        let watched = task_ids.len().min(MAX_LOCATION_WATCHED_TASKS);
+        self.watch_task_details(app_id, &task_ids[..watched]).await;
+        self.state.get_merged_histograms(app_id, &task_ids).await
+    }
+
+    pub(crate) async fn watch_task_details(&self, app_id: Uuid, task_ids: &[u64]) {
+        if let Some(collector) = &self.collector {
+            let request = Request::WatchTaskDetails {
+                app_id,
+                task_ids: task_ids.to_vec(),
+            };
+            if let Err(err) = collector.request::<()>(request).await {
+                error!("Cannot watch the details of tasks of application {app_id} due to {err:?}");
+            }
+            return;
+        }
+
+        let applications = self.state.get_current_applications_list().await;
+        let Some(connection) = applications
+            .iter()
+            .find(|application| *application.id() == app_id)
+            .and_then(|application| application.connection())
+        else {
+            return;
+        };
+
+        for task_id in task_ids {
+            connection
+                .commands
+                .send(Command::WatchTaskDetails(*task_id))
+                .await
+                .ok();
+        }
+    }
+
+    /// Writes the current data of an application to a file
+    pub async fn export_application(
+        &self,
+        app_id: Uuid,
+        format: ExportFormat,
+        path: &Path,
+    ) -> Result<(), TraceError> {
+        let application = self
+            .state
+            .get_current_applications_list()
+            .await
+            .into_iter()
+            .find(|application| *application.id() == app_id)
+            .ok_or(TraceError::ApplicationNotFound(app_id))?;
+
+        let snapshot = Snapshot {
+            application,
+            tasks: self
+                .state
+                .get_tasks()
+                .await
+                .into_iter()
+                .filter(|task| task.app_id == app_id)
+                .collect(),
+            resources: self
+                .state
+                .get_resources()
+                .await
+                .into_iter()
+                .filter(|resource| resource.app_id == app_id)
+                .collect(),
+            poll_spans: self.state.get_poll_spans(app_id).await,
+        };
+
+        info!("Exporting application {app_id} as {format:?} to {path:?}");
+        tokio::fs::write(path, export(&snapshot, format, path)?)
+            .await
+            .map_err(|err| TraceError::CannotWrite {
+                error: err.into(),
+                path: path.to_string_lossy().to_string(),
+            })
+    }
+
+    /// Starts re-exposing an application to other consoles on the given port,
+    /// or stops if no port is given
+    pub async fn set_application_proxy(
+        self: &Arc<Self>,
+        uuid: Uuid,
+        port: Option<u16>,
+    ) -> Result<(), TraceError> {
+        if let Some(collector) = &self.collector {
+            return collector
+                .request(Request::SetApplicationProxy { app_id: uuid, port })
+                .await;
+        }
+
+        let application = self
+            .state
+            .get_current_applications_list()
+            .await
+            .into_iter()
+            .find(|application| *application.id() == uuid)
+            .ok_or(TraceError::ApplicationNotFound(uuid))?;
+
+        let mut proxies = self.proxies.lock().await;
+        proxies.remove(&uuid);
+        match port {
+            Some(port) => {
+                let proxy = start_proxy(port, uuid, self.clone()).await?;
+                info!(
+                    "Application {uuid} re-exposed to other consoles on http://{}",
+                    proxy.address()
+                );
+                proxies.insert(uuid, proxy);
+
+                // The mirror is seeded with the whole state sent again by the application
+                self.proxy_hub.start_mirror(uuid).await;
+                if let Some(connection) = application.connection() {
+                    connection.commands.send(Command::WatchUpdates).await.ok();
+                }
+            }
+            None => self.proxy_hub.stop_mirror(uuid).await,
+        }
+
+        self.state.set_app_proxy_port(uuid, port).await
+    }
+
+    /// Removes the completed tasks and the metrics history of an application
+    pub async fn purge_application_history(&self, uuid: Uuid) -> Result<(), TraceError> {
+        if let Some(collector) = &self.collector {
+            collector
+                .request::<()>(Request::PurgeHistory { app_id: uuid })
+                .await?;
+        }
+
+        let applications = self.state.get_current_applications_list().await;
+        if !applications
+            .iter()
+            .any(|application| *application.id() == uuid)
+        {
+            return Err(TraceError::ApplicationNotFound(uuid));
+        }
+
+        info!("Purging the history of application {uuid}");
+        self.state.purge_app_history(uuid).await;
+        Ok(())
+    }
+
     pub async fn delete_connection(&self, uuid: Uuid) {
+        if let Some(child) = self.launched.lock().await.remove(&uuid) {
+            info!("Killing the process of application {uuid}");
+            child.kill().ok();
+        }
+        if let Some(collector) = &self.collector {
+            let request = Request::DeleteApplication { app_id: uuid };
+            if let Err(err) = collector.request::<()>(request).await {
+                error!("Cannot delete application {uuid} due to {err:?}");
+            }
+        }
+
+        self.proxies.lock().await.remove(&uuid);
+        self.proxy_hub.remove_app(uuid).await;
+        self.leak_detector.forget(uuid);
+        self.blocking_detector.forget(uuid);
         self.connection_manager.disconnect_app(uuid).await;
         self.state.delete_app(uuid).await
     }
 
+    /// Searches the console servers running on the machine
+    pub async fn discover_applications(&self) -> Vec<DiscoveredEndpoint> {
+        let settings = self.state.get_settings().await;
+        let applications = self.state.get_current_applications_list().await;
+        discover(&settings.discovery, &applications).await
+    }
+
+    // endregion
+
+    // region launched applications
+
+    /// Spawns a program instrumented with console-subscriber, listening on
+    /// a free port, then registers it and connects to it
+    ///
+    /// The connection is retried until the program listens on the port.
+    pub async fn launch_application(
+        self: &Arc<Self>,
+        app_handle: &AppHandle,
+        title: Option<String>,
+        program: String,
+        args: Vec<String>,
+        current_dir: Option<PathBuf>,
+    ) -> Result<Uuid, TraceError> {
+        let cannot_launch = |error: anyhow::Error| TraceError::CannotLaunch {
+            error,
+            program: program.clone(),
+        };
+
+        // The port is released right away, for the program to listen on it
+        let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .await
+            .and_then(|listener| listener.local_addr())
+            .map_err(|error| cannot_launch(error.into()))?
+            .port();
+        let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+
+        let mut command = app_handle
+            .shell()
+            .command(&program)
+            .args(&args)
+            .env(CONSOLE_BIND_VARIABLE, address.to_string());
+        if let Some(current_dir) = current_dir {
+            command = command.current_dir(current_dir);
+        }
+        let (events, child) = command
+            .spawn()
+            .map_err(|error| cannot_launch(error.into()))?;
+        info!(
+            "Launched {program} with pid {}, console listening on {address}",
+            child.pid()
+        );
+
+        let title = title.unwrap_or_else(|| {
+            Path::new(&program)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| program.clone())
+        });
+        let url = Url::parse(&format!("http://{address}"))?;
+        let app_id = match self.add_application(title, url, None, HashMap::new()).await {
+            Ok(app_id) => app_id,
+            Err(error) => {
+                child.kill().ok();
+                return Err(error);
+            }
+        };
+        self.launched.lock().await.insert(app_id, child);
+
+        let state_manager = self.clone();
+        let app_handle = app_handle.clone();
+        tokio::spawn(async move {
+            state_manager
+                .supervise_process(&app_handle, app_id, events)
+                .await;
+        });
+
+        Ok(app_id)
+    }
+
+    /// Records the output of a launched application until its process ends
+    async fn supervise_process(
+        &self,
+        app_handle: &AppHandle,
+        app_id: Uuid,
+        mut events: async_runtime::Receiver<CommandEvent>,
+    ) {
+        while let Some(event) = events.recv().await {
+            let (stream, bytes) = match event {
+                CommandEvent::Stdout(bytes) => (OutputStream::Stdout, bytes),
+                CommandEvent::Stderr(bytes) => (OutputStream::Stderr, bytes),
+                CommandEvent::Terminated(payload) => {
+                    info!("Process of application {app_id} ended with {payload:?}");
+                    self.launched.lock().await.remove(&app_id);
+                    self.state.exit_app(app_id, payload.code).await;
+                    break;
+                }
+                CommandEvent::Error(error) => {
+                    warn!("Cannot read the output of application {app_id} due to {error}");
+                    continue;
+                }
+                _ => continue,
+            };
+
+            let line = OutputLine {
+                stream,
+                line: String::from_utf8_lossy(&bytes)
+                    .trim_end_matches(['\n', '\r'])
+                    .to_owned(),
+                at: SystemTime::now(),
+            };
+            app_handle
+                .emit(
+                    "output:application",
+                    OutputEvent {
+                        app_id,
+                        line: &line,
+                    },
+                )
+                .ok();
+            self.state.add_output_line(app_id, line).await;
+        }
+    }
+
+    /// Returns the latest lines written by a launched application
+    pub async fn application_output(&self, app_id: Uuid) -> Vec<OutputLine> {
+        self.state.get_output(app_id).await
+    }
+
+    // endregion
+
+    // region settings
+
+    /// Returns the stored settings, the collector's ones when attached
+    pub async fn settings(&self) -> Result<Settings, TraceError> {
+        if let Some(collector) = &self.collector {
+            let settings: Settings = collector.request(Request::GetSettings).await?;
+            // Another window may have changed them
+            self.state.store_settings(settings.clone()).await;
+            return Ok(settings);
+        }
+
+        Ok(self.state.get_settings().await)
+    }
+
+    /// Stores the new settings and restarts the endpoints whose settings changed
+    ///
+    /// When attached, the collector stores the settings and runs the endpoints
+    ///
+    /// # Error
+    ///
+    /// If the settings are invalid or an endpoint cannot be started, an
+    /// error will be returned and the settings will not be stored
+    pub async fn update_settings(self: &Arc<Self>, settings: Settings) -> Result<(), TraceError> {
+        settings.discovery.validate()?;
+
+        if let Some(collector) = &self.collector {
+            let request = Request::UpdateSettings {
+                settings: settings.clone(),
+            };
+            collector.request::<()>(request).await?;
+            self.state.store_settings(settings).await;
+            return Ok(());
+        }
+
+        let current = self.state.get_settings().await;
+        if settings.metrics_endpoint != current.metrics_endpoint {
+            self.start_metrics_endpoint(&settings.metrics_endpoint)
+                .await?;
+        }
+        if settings.api != current.api {
+            self.start_api_endpoint(&settings.api).await?;
+        }
+
+        self.state.store_settings(settings).await;
+        Ok(())
+    }
+
+    /// Starts the endpoints enabled in the stored settings, and the
+    /// proxies of the applications
+    ///
+    /// Every endpoint is started even if another one cannot be, the failures
+    /// are logged
+    pub async fn start_endpoints(self: &Arc<Self>) {
+        for application in self.state.get_current_applications_list().await {
+            if let Some(port) = application.proxy_port() {
+                let app_id = *application.id();
+                if let Err(err) = self.set_application_proxy(app_id, Some(port)).await {
+                    error!("Cannot start the proxy of application {app_id} due to {err:?}");
+                }
+            }
+        }
+
+        let settings = self.state.get_settings().await;
+        if let Err(err) = self
+            .start_metrics_endpoint(&settings.metrics_endpoint)
+            .await
+        {
+            error!("Cannot start the metrics endpoint due to {err:?}");
+        }
+        if let Err(err) = self.start_api_endpoint(&settings.api).await {
+            error!("Cannot start the API endpoint due to {err:?}");
+        }
+    }
+
+    async fn start_metrics_endpoint(
+        self: &Arc<Self>,
+        settings: &MetricsEndpointSettings,
+    ) -> Result<(), TraceError> {
+        let mut metrics_endpoint = self.metrics_endpoint.lock().await;
+        // Stop the previous endpoint first, it may listen on the same port
+        *metrics_endpoint = None;
+        if settings.enabled {
+            let server =
+                LocalServer::start(settings.port, prometheus::router(self.clone())).await?;
+            info!(
+                "Prometheus metrics exported on http://{}/metrics",
+                server.address()
+            );
+            *metrics_endpoint = Some(server);
+        }
+        Ok(())
+    }
+
+    async fn start_api_endpoint(
+        self: &Arc<Self>,
+        settings: &ApiSettings,
+    ) -> Result<(), TraceError> {
+        let mut api_endpoint = self.api_endpoint.lock().await;
+        *api_endpoint = None;
+        if settings.enabled {
+            if settings.token.is_empty() {
+                return Err(TraceError::InvalidSettings(
+                    "the API token cannot be empty".to_owned(),
+                ));
+            }
+            let router = api::router(self.clone(), settings.token.clone());
+            let server = LocalServer::start(settings.port, router).await?;
+            info!("API served on http://{}/api", server.address());
+            *api_endpoint = Some(server);
+        }
+        Ok(())
+    }
+
     // endregion
 
     // region UPDATES
 
+    /// Returns a receiver of the events sent to the user interface
+    pub fn subscribe_live_updates(&self) -> broadcast::Receiver<LiveUpdate> {
+        self.live_updates.subscribe()
+    }
+
+    /// Publishes an event to the API clients, if any is listening
+    fn publish_live_update(&self, update: impl FnOnce() -> LiveUpdate) {
+        if self.live_updates.receiver_count() > 0 {
+            self.live_updates.send(update()).ok();
+        }
+    }
+
+    /// Emits the live tasks, the completed ones are available through the queries
     pub async fn emit_update_tasks(&self, app_handle: &AppHandle) {
-        let tasks = self.state.get_tasks().await;
+        let tasks = self.state.get_live_tasks().await;
         info!("Sending tasks update event with {} tasks", tasks.len());
+        self.publish_live_update(|| LiveUpdate::Tasks(tasks.clone()));
         app_handle.emit("update:tasks", tasks).ok();
     }
 
     pub async fn emit_update_applications(&self, app_handle: &AppHandle) {
-        app_handle
-            .emit(
-                "update:applications",
-                self.state.get_current_applications_list().await,
-            )
-            .ok();
+        let applications = self.state.get_current_applications_list().await;
+        self.publish_live_update(|| LiveUpdate::Applications(applications.clone()));
+        app_handle.emit("update:applications", applications).ok();
+    }
+
+    /// Emits the groups of the watched grouping
+    pub async fn emit_task_groups(&self, app_handle: &AppHandle) {
+        let Some(grouping) = self.watched_grouping.lock().await.clone() else {
+            return;
+        };
+        match self.group_tasks(&self.watched_grouper, &grouping).await {
+            Ok(groups) => {
+                self.publish_live_update(|| LiveUpdate::TaskGroups(groups.clone()));
+                app_handle.emit("update:task_groups", groups).ok();
+            }
+            Err(err) => warn!("Cannot group the tasks due to {err:?}"),
+        }
+    }
+
+    /// Emits the data loss of the applications which dropped events, when
+    /// changed since the previous call
+    pub async fn emit_data_loss_warnings(&self, app_handle: &AppHandle) {
+        let data_loss = self.state.get_data_loss().await;
+        let current = data_loss
+            .iter()
+            .map(|(app_id, data_loss)| (*app_id, (data_loss.dropped, data_loss.incomplete)))
+            .collect();
+        let changed = {
+            let mut emitted = self.emitted_data_loss.lock().await;
+            let changed = *emitted != current;
+            *emitted = current;
+            changed
+        };
+
+        if changed && !data_loss.is_empty() {
+            self.publish_live_update(|| LiveUpdate::DataLoss(data_loss.clone()));
+            app_handle.emit("warning:data_loss", data_loss).ok();
+        }
+    }
+
+    /// Emits the polls which exceeded the budget since the previous call
+    pub async fn emit_blocking_warnings(&self, app_handle: &AppHandle) {
+        // The completed tasks are kept as offenders until removed
+        let tasks = self.state.get_tasks().await;
+        let settings = self.state.get_settings().await.blocking_detection;
+        let budget = Duration::from_millis(settings.poll_budget);
+
+        let mut incidents = Vec::new();
+        for (app_id, now) in self.updated_applications().await {
+            let poll_spans = self.state.get_poll_spans(app_id).await;
+            let histograms = self.state.get_app_histograms(app_id).await;
+            incidents.extend(self.blocking_detector.analyze(
+                app_id,
+                now,
+                &tasks,
+                &poll_spans,
+                &histograms,
+                budget,
+            ));
+        }
+
+        if !incidents.is_empty() {
+            warn!(
+                "Sending warning event with {} polls exceeding the budget",
+                incidents.len()
+            );
+            self.publish_live_update(|| LiveUpdate::Blocking(incidents.clone()));
+            app_handle.emit("warning:blocking", incidents).ok();
+        }
+    }
+
+    /// Emits the suspected task leaks that were not reported yet
+    pub async fn emit_leak_warnings(&self, app_handle: &AppHandle) {
+        let tasks = self.state.get_live_tasks().await;
+        let settings = self.state.get_settings().await.leak_detection;
+
+        let mut reports = Vec::new();
+        for (app_id, now) in self.updated_applications().await {
+            reports.extend(self.leak_detector.analyze(app_id, now, &tasks, &settings));
+        }
+
+        let reports = self.leak_detector.retain_new(reports);
+        if !reports.is_empty() {
+            warn!(
+                "Sending warning event with {} suspected task leaks",
+                reports.len()
+            );
+            self.publish_live_update(|| LiveUpdate::TaskLeaks(reports.clone()));
+            app_handle.emit("warning:task_leak", reports).ok();
+        }
+    }
+
+    /// Returns the applications receiving updates, with the time of their last
+    /// update (in the application's clock)
+    ///
+    /// The analyzers use the time of the last update, so that the tasks of the
+    /// disconnected applications do not seem to stay idle or be polled forever.
+    async fn updated_applications(&self) -> Vec<(Uuid, SystemTime)> {
+        let mut applications = Vec::new();
+        for application in self.state.get_current_applications_list().await {
+            let app_id = *application.id();
+            if !matches!(
+                application.state(),
+                ApplicationState::Enabled | ApplicationState::Offline
+            ) {
+                continue;
+            }
+            if let Some(last_update) = self.state.get_last_update(app_id).await {
+                applications.push((app_id, last_update));
+            }
+        }
+        applications
+    }
+
+    /// Emits the suspected deadlocks that were not reported yet
+    pub async fn emit_deadlock_warnings(&self, app_handle: &AppHandle) {
+        let tasks = self.state.get_live_tasks().await;
+        let resources = self.state.get_resources().await;
+        let async_ops = self.state.get_async_ops().await;
+
+        let mut reports = Vec::new();
+        for application in self.state.get_current_applications_list().await {
+            let app_id = *application.id();
+            if let Some(now) = self.state.get_app_now(app_id).await {
+                reports.extend(
+                    self.deadlock_detector
+                        .analyze(app_id, now, &tasks, &resources, &async_ops),
+                );
+            }
+        }
+
+        let reports = self.deadlock_detector.retain_new(reports);
+        if !reports.is_empty() {
+            warn!(
+                "Sending warning event with {} suspected deadlocks",
+                reports.len()
+            );
+            self.publish_live_update(|| LiveUpdate::Deadlocks(reports.clone()));
+            app_handle.emit("warning:deadlock", reports).ok();
+        }
     }
 
     // endregion
 }
+
+/// Start of a window ending now, in milliseconds since UNIX epoch, as the
+/// metrics history is timestamped in the local clock
+fn window_start(window: Duration) -> u64 {
+    SystemTime::now()
+        .checked_sub(window)
+        .and_then(|start| start.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|start| start.as_millis() as u64)
+        .unwrap_or_default()
+}
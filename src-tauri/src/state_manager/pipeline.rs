@@ -0,0 +1,287 @@
+//! Queues of the events received from the connections, one per application
+//!
+//! Connections never wait for the events to be processed: consecutive updates
+//! are merged while queued, so a slow processing only delays the state and
+//! never the network reads. The queues are processed concurrently.
+
+use super::connection_manager::Event;
+use console_api::instrument::Update;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    sync::{mpsc, Notify},
+    time::Instant,
+};
+use uuid::Uuid;
+
+/// Events kept for an application, the oldest are discarded first past it
+///
+/// Updates and task details are merged, so this is only reached if the
+/// connection keeps changing status
+const MAX_QUEUED_EVENTS: usize = 1_000;
+
+/// Ingestion of the events of an application
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct IngestionStats {
+    /// Events waiting to be processed
+    pub queued: usize,
+    pub received: u64,
+    /// Events merged into a queued one
+    pub coalesced: u64,
+    /// Events discarded as the queue was full
+    pub discarded: u64,
+    /// Time the last processed event waited in the queue
+    pub lag: Duration,
+    pub max_lag: Duration,
+}
+
+struct Queued {
+    event: Event,
+    queued_at: Instant,
+    /// Local time the event was received at, of the latest one if merged
+    received_at: SystemTime,
+}
+
+#[derive(Default)]
+struct Queue {
+    events: VecDeque<Queued>,
+    stats: IngestionStats,
+    closed: bool,
+    /// Closed and processed, the events sent afterwards need another queue
+    finished: bool,
+}
+
+impl Queue {
+    /// Merges the event into a queued one if possible, returns it otherwise
+    fn coalesce(&mut self, event: Event) -> Option<Event> {
+        // Events are not merged across connection changes
+        let pending =
+            self.events.iter_mut().rev().take_while(|queued| {
+                matches!(queued.event, Event::Update(_) | Event::TaskDetails(_))
+            });
+
+        match event {
+            Event::Update(update) => {
+                for queued in pending {
+                    if let Event::Update(queued_update) = &mut queued.event {
+                        // The merged update has the time of the newer one
+                        merge_updates(queued_update, update);
+                        queued.received_at = SystemTime::now();
+                        return None;
+                    }
+                }
+                Some(Event::Update(update))
+            }
+            Event::TaskDetails(details) => {
+                for queued in pending {
+                    if let Event::TaskDetails(queued_details) = &mut queued.event {
+                        if queued_details.task_id == details.task_id {
+                            // Histograms are cumulative, the latest replace the previous
+                            *queued_details = details;
+                            return None;
+                        }
+                    }
+                }
+                Some(Event::TaskDetails(details))
+            }
+            event => Some(event),
+        }
+    }
+}
+
+/// Queue of the events of an application
+pub(crate) struct AppQueue {
+    queue: Mutex<Queue>,
+    notify: Notify,
+}
+
+impl AppQueue {
+    fn push(&self, event: Event) {
+        {
+            let mut queue = self.queue.lock().unwrap();
+            queue.stats.received += 1;
+            match queue.coalesce(event) {
+                None => queue.stats.coalesced += 1,
+                Some(event) => {
+                    if queue.events.len() == MAX_QUEUED_EVENTS {
+                        queue.events.pop_front();
+                        queue.stats.discarded += 1;
+                    }
+                    queue.events.push_back(Queued {
+                        event,
+                        queued_at: Instant::now(),
+                        received_at: SystemTime::now(),
+                    });
+                }
+            }
+            queue.stats.queued = queue.events.len();
+        }
+        self.notify.notify_one();
+    }
+
+    fn close(&self) {
+        self.queue.lock().unwrap().closed = true;
+        self.notify.notify_one();
+    }
+
+    /// Waits for the next event and the local time it was received at,
+    /// returns none once closed and empty
+    async fn pop(&self) -> Option<(Event, SystemTime)> {
+        loop {
+            {
+                let mut queue = self.queue.lock().unwrap();
+                if let Some(queued) = queue.events.pop_front() {
+                    let lag = queued.queued_at.elapsed();
+                    queue.stats.lag = lag;
+                    queue.stats.max_lag = queue.stats.max_lag.max(lag);
+                    queue.stats.queued = queue.events.len();
+                    return Some((queued.event, queued.received_at));
+                }
+                if queue.closed {
+                    queue.finished = true;
+                    return None;
+                }
+            }
+            self.notify.notified().await;
+        }
+    }
+}
+
+struct Pipeline {
+    queues: Mutex<HashMap<Uuid, Arc<AppQueue>>>,
+    new_queues: mpsc::UnboundedSender<(Uuid, Arc<AppQueue>)>,
+}
+
+/// Sends the events of the connections, without ever waiting
+#[derive(Clone)]
+pub struct EventSender(Arc<Pipeline>);
+
+/// Receives the events of every application, once it sent its first event
+pub struct EventReceiver(mpsc::UnboundedReceiver<(Uuid, Arc<AppQueue>)>);
+
+/// Events of an application, in the order they were sent
+pub struct AppEvents(Arc<AppQueue>);
+
+pub fn pipeline() -> (EventSender, EventReceiver) {
+    let (new_queues, receiver) = mpsc::unbounded_channel();
+    let pipeline = Pipeline {
+        queues: Mutex::new(HashMap::new()),
+        new_queues,
+    };
+    (EventSender(Arc::new(pipeline)), EventReceiver(receiver))
+}
+
+impl EventSender {
+    pub fn send(&self, app_id: Uuid, event: Event) {
+        let queue = {
+            let mut queues = self.0.queues.lock().unwrap();
+            // A closed queue which is still processed is reopened, so that the
+            // events of an application are never processed concurrently
+            let reopened = queues.get(&app_id).filter(|queue| {
+                let mut queue = queue.queue.lock().unwrap();
+                if queue.finished {
+                    return false;
+                }
+                queue.closed = false;
+                true
+            });
+            match reopened {
+                Some(queue) => queue.clone(),
+                None => {
+                    let queue = Arc::new(AppQueue {
+                        queue: Mutex::new(Queue::default()),
+                        notify: Notify::new(),
+                    });
+                    self.0.new_queues.send((app_id, queue.clone())).ok();
+                    queues.insert(app_id, queue.clone());
+                    queue
+                }
+            }
+        };
+        queue.push(event);
+    }
+
+    /// Stops the queue of the application once its events are processed
+    pub fn close(&self, app_id: Uuid) {
+        if let Some(queue) = self.0.queues.lock().unwrap().get(&app_id) {
+            queue.close();
+        }
+    }
+
+    pub fn stats(&self) -> HashMap<Uuid, IngestionStats> {
+        self.0
+            .queues
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(app_id, queue)| {
+                let queue = queue.queue.lock().unwrap();
+                (!queue.closed).then(|| (*app_id, queue.stats.clone()))
+            })
+            .collect()
+    }
+}
+
+impl EventReceiver {
+    pub async fn next_app(&mut self) -> Option<(Uuid, AppEvents)> {
+        self.0
+            .recv()
+            .await
+            .map(|(app_id, queue)| (app_id, AppEvents(queue)))
+    }
+}
+
+impl AppEvents {
+    /// Returns the next event and the local time it was received at
+    pub async fn recv(&self) -> Option<(Event, SystemTime)> {
+        self.0.pop().await
+    }
+}
+
+/// Merges a newer update into an older one, the latest stats of every
+/// task, resource and async op are kept
+fn merge_updates(older: &mut Update, newer: Update) {
+    older.now = newer.now.or(older.now.take());
+
+    if let Some(newer) = newer.task_update {
+        match &mut older.task_update {
+            Some(older) => {
+                older.new_tasks.extend(newer.new_tasks);
+                older.stats_update.extend(newer.stats_update);
+                older.dropped_events += newer.dropped_events;
+            }
+            None => older.task_update = Some(newer),
+        }
+    }
+    if let Some(newer) = newer.resource_update {
+        match &mut older.resource_update {
+            Some(older) => {
+                older.new_resources.extend(newer.new_resources);
+                older.stats_update.extend(newer.stats_update);
+                older.new_poll_ops.extend(newer.new_poll_ops);
+                older.dropped_events += newer.dropped_events;
+            }
+            None => older.resource_update = Some(newer),
+        }
+    }
+    if let Some(newer) = newer.async_op_update {
+        match &mut older.async_op_update {
+            Some(older) => {
+                older.new_async_ops.extend(newer.new_async_ops);
+                older.stats_update.extend(newer.stats_update);
+                older.dropped_events += newer.dropped_events;
+            }
+            None => older.async_op_update = Some(newer),
+        }
+    }
+    if let Some(newer) = newer.new_metadata {
+        match &mut older.new_metadata {
+            Some(older) => older.metadata.extend(newer.metadata),
+            None => older.new_metadata = Some(newer),
+        }
+    }
+}
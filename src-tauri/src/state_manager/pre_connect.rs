@@ -0,0 +1,130 @@
+//! Commands run before connecting to an application (eg: `kubectl port-forward`
+//! or `ssh -L`), kept running while the application is connected
+
+use crate::domain::application::PreConnectHook;
+use crate::error::Error as TraceError;
+use log::info;
+use std::{process::Stdio, time::Duration};
+use tauri::Url;
+use tokio::{
+    io::AsyncReadExt,
+    net::TcpStream,
+    process::{Child, ChildStderr, Command},
+    task::JoinHandle,
+    time::{sleep, Instant},
+};
+
+/// Delay between two checks of the port of the application
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Number of bytes of the error output kept, the last ones are kept
+const MAX_STDERR_SIZE: usize = 4 * 1024;
+
+/// Running pre-connect command, killed once dropped
+pub(crate) struct HookProcess {
+    program: String,
+    child: Child,
+    stderr: Option<JoinHandle<String>>,
+}
+
+impl HookProcess {
+    /// Waits for the command to exit and returns why it failed
+    pub async fn failure(&mut self) -> TraceError {
+        let reason = match self.child.wait().await {
+            Ok(status) => format!("exited with {status}"),
+            Err(error) => format!("cannot be waited for ({error})"),
+        };
+        self.error(reason).await
+    }
+
+    /// Whether the command did not exit yet
+    pub fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    /// Kills the command and returns an error holding its error output
+    async fn error(&mut self, reason: String) -> TraceError {
+        self.child.kill().await.ok();
+        let stderr = match self.stderr.take() {
+            Some(stderr) => stderr.await.unwrap_or_default(),
+            None => String::new(),
+        };
+
+        TraceError::PreConnectFailed {
+            program: self.program.clone(),
+            reason,
+            stderr,
+        }
+    }
+}
+
+/// Keeps the end of the error output of the command
+async fn read_stderr(mut stderr: ChildStderr) -> String {
+    let mut output = Vec::new();
+    let mut buffer = [0; 1024];
+    while let Ok(read) = stderr.read(&mut buffer).await {
+        if read == 0 {
+            break;
+        }
+        output.extend_from_slice(&buffer[..read]);
+        if output.len() > MAX_STDERR_SIZE {
+            output.drain(..output.len() - MAX_STDERR_SIZE);
+        }
+    }
+    String::from_utf8_lossy(&output).trim().to_owned()
+}
+
+/// Runs the command and waits for the port of the application to accept connections
+///
+/// # Error
+///
+/// If the command cannot be run, exits or the port is not opened in time,
+/// an error holding the error output of the command will be returned
+pub(crate) async fn start(hook: &PreConnectHook, url: &Url) -> Result<HookProcess, TraceError> {
+    let failed = |reason: String| TraceError::PreConnectFailed {
+        program: hook.program.clone(),
+        reason,
+        stderr: String::new(),
+    };
+
+    let host = url.host_str().unwrap_or("localhost").to_owned();
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| failed(format!("cannot wait for {url}, it has no port")))?;
+
+    let mut child = Command::new(&hook.program)
+        .args(&hook.args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|error| failed(format!("cannot be run ({error})")))?;
+    info!("Started pre-connect command {} for {url}", hook.program);
+
+    let stderr = child
+        .stderr
+        .take()
+        .map(|stderr| tokio::spawn(read_stderr(stderr)));
+    let mut process = HookProcess {
+        program: hook.program.clone(),
+        child,
+        stderr,
+    };
+
+    let deadline = Instant::now() + Duration::from_secs(hook.ready_timeout);
+    loop {
+        if TcpStream::connect((host.as_str(), port)).await.is_ok() {
+            info!("Pre-connect command {} is ready", hook.program);
+            return Ok(process);
+        }
+        if let Ok(Some(status)) = process.child.try_wait() {
+            return Err(process.error(format!("exited with {status}")).await);
+        }
+        if Instant::now() >= deadline {
+            let reason = format!("did not open port {port} in {}s", hook.ready_timeout);
+            return Err(process.error(reason).await);
+        }
+        sleep(READY_POLL_INTERVAL).await;
+    }
+}
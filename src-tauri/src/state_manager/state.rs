@@ -1,22 +1,96 @@
+use super::clock::Clock;
+use super::connection_manager::Connection;
 use super::database::Database;
-use crate::domain::application::ApplicationState;
-use crate::error::Error as TraceError;
+use super::history::{Metrics, MetricsHistory, MetricsSample};
+use crate::domain::application::{ApplicationState, ConnectionStatus, OutputLine};
+use crate::domain::data_loss::{DataLoss, DroppedEvents};
+use crate::error::{Error as TraceError, ErrorReport};
 use crate::infra::guard::DataBaseWrite;
-use crate::infra::storage::Storage;
+use crate::infra::storage::{Storage, Tasks};
 use crate::{
-    domain::{application::Application, Task},
-    mappers::tasks::map_to_domain_task,
+    domain::{
+        application::Application,
+        settings::{RetentionSettings, Settings},
+        AsyncOp, PollSpan, Resource, Task, TaskHistograms,
+    },
+    mappers::{
+        async_ops::{map_to_domain_async_op, update_domain_async_op},
+        histograms::map_to_domain_histograms,
+        map_timestamp,
+        resources::{map_to_domain_resource, update_domain_resource},
+        tasks::{map_to_domain_task, update_domain_task},
+    },
+};
+use console_api::{
+    async_ops::AsyncOpUpdate,
+    resources::ResourceUpdate,
+    tasks::{TaskDetails, TaskUpdate},
 };
-use console_api::tasks::TaskUpdate;
 use log::{error, info, warn};
-use std::sync::Arc;
-use tokio::fs;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+use tokio::{fs, sync::RwLock};
 use uuid::Uuid;
 
+/// Number of polls kept for every task
+const MAX_POLL_SPANS: usize = 1_000;
+
+/// Number of output lines kept for every launched application
+const MAX_OUTPUT_LINES: usize = 5_000;
+
+/// Size of a stored task without its strings: the ids, the field names
+/// and the stats
+const TASK_FIXED_SIZE: u64 = 700;
+
+/// Polls of the tasks which are not stored anymore, so that the totals of an
+/// application never decrease
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct RemovedTasks {
+    pub polls: u64,
+    pub busy_time: Duration,
+}
+
+impl RemovedTasks {
+    fn add(&mut self, task: &Task) {
+        self.polls += task.stats.polls;
+        self.busy_time += task.stats.busy_time;
+    }
+}
+
 /// Is managing the access to the database and provides access method
 /// tailored for the applications business locic needs
 pub struct State {
     database: Arc<dyn Storage>,
+
+    // Resources and async ops are only relevant while the application
+    // runs, so they are kept in memory only
+    resources: RwLock<HashMap<String, Arc<Resource>>>,
+    async_ops: RwLock<HashMap<String, Arc<AsyncOp>>>,
+    // Clock of every application, estimated from the time of its updates
+    clocks: RwLock<HashMap<Uuid, Clock>>,
+    history: RwLock<MetricsHistory>,
+    // Latest histograms of the tasks whose details are watched
+    histograms: RwLock<HashMap<String, Arc<TaskHistograms>>>,
+    // Latest polls observed for every task
+    poll_spans: RwLock<HashMap<String, VecDeque<PollSpan>>>,
+    connection_statuses: RwLock<HashMap<Uuid, ConnectionStatus>>,
+    // Why the latest connection attempt failed, cleared once connected
+    connection_errors: RwLock<HashMap<Uuid, ErrorReport>>,
+    // Events the applications reported as dropped, since connected
+    data_loss: RwLock<HashMap<Uuid, DataLoss>>,
+    // Latest lines written by the launched applications
+    outputs: RwLock<HashMap<Uuid, VecDeque<OutputLine>>>,
+    // Estimated size of the stored tasks, kept up to date by the writers
+    tasks_size: AtomicU64,
+    // Polls of the tasks removed from every application
+    removed_tasks: RwLock<HashMap<Uuid, RemovedTasks>>,
 }
 
 impl State {
@@ -28,12 +102,17 @@ impl State {
     ///
     /// Should be used in case of failure when loading
     pub fn new() -> Self {
-        let path = dirs::home_dir().unwrap().join(Self::STORAGE_FOLDER);
+        let path = Self::storage_folder();
         info!("Storage location is: {path:?}");
 
-        Self {
-            database: Arc::new(Database::new(path.as_path().to_string_lossy().to_string())),
-        }
+        Self::with_database(Database::new(path.as_path().to_string_lossy().to_string()))
+    }
+
+    /// Creates a new, fresh instance which never writes to the disk
+    ///
+    /// Used when attached to a collector, which owns the storage
+    pub fn in_memory() -> Self {
+        Self::with_database(Database::in_memory())
     }
 
     /// Is loading state from previus application instance
@@ -42,7 +121,7 @@ impl State {
     ///
     /// If failed to load data from disk, will return an error
     pub async fn load() -> Result<State, TraceError> {
-        let database_path = dirs::home_dir().unwrap().join(Self::STORAGE_FOLDER);
+        let database_path = Self::storage_folder();
         info!("Storage location is: {database_path:?}");
 
         // Checking if storage folder exists
@@ -60,9 +139,35 @@ impl State {
         let database =
             Database::load(database_path.as_path().to_string_lossy().to_string()).await?;
 
-        Ok(State {
+        Ok(Self::with_database(database))
+    }
+
+    fn with_database(database: Database) -> Self {
+        let tasks_size = database
+            .loaded_tasks()
+            .values()
+            .map(|task| stored_size(task))
+            .sum();
+        Self {
             database: Arc::new(database),
-        })
+            resources: RwLock::new(HashMap::new()),
+            async_ops: RwLock::new(HashMap::new()),
+            clocks: RwLock::new(HashMap::new()),
+            history: RwLock::new(MetricsHistory::default()),
+            histograms: RwLock::new(HashMap::new()),
+            poll_spans: RwLock::new(HashMap::new()),
+            connection_statuses: RwLock::new(HashMap::new()),
+            connection_errors: RwLock::new(HashMap::new()),
+            data_loss: RwLock::new(HashMap::new()),
+            outputs: RwLock::new(HashMap::new()),
+            tasks_size: AtomicU64::new(tasks_size),
+            removed_tasks: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the folder where the data of the application is stored
+    pub fn storage_folder() -> PathBuf {
+        dirs::home_dir().unwrap().join(Self::STORAGE_FOLDER)
     }
 
     // region APPLICATIONS
@@ -97,8 +202,140 @@ impl State {
         Ok(())
     }
 
+    /// Enables a stored application again, with a new connection
+    pub async fn enable_app(&self, uuid: Uuid, connection: Connection) -> Result<(), TraceError> {
+        let mut guard = self.database.applications_write().await;
+        let application = guard
+            .get_mut(&uuid)
+            .ok_or(TraceError::ApplicationNotFound(uuid))?;
+        application.writeable().enable(connection);
+
+        Ok(())
+    }
+
+    pub async fn exit_app(&self, uuid: Uuid, exit_code: Option<i32>) {
+        if let Some(application) = self.database.applications_write().await.get_mut(&uuid) {
+            application.writeable().exit(exit_code).await;
+        }
+        self.pause_clock(uuid).await;
+    }
+
+    pub async fn add_output_line(&self, app_id: Uuid, line: OutputLine) {
+        let mut outputs = self.outputs.write().await;
+        let lines = outputs.entry(app_id).or_default();
+        if lines.len() == MAX_OUTPUT_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    pub async fn get_output(&self, app_id: Uuid) -> Vec<OutputLine> {
+        self.outputs
+            .read()
+            .await
+            .get(&app_id)
+            .map(|lines| lines.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Replaces all the applications, eg: with the ones of the collector
+    ///
+    /// The offline applications are kept, as the recordings are imported
+    /// by the windows and never known to the collector
+    pub async fn replace_apps(&self, applications: Vec<Application>) {
+        let mut stored = self.database.applications_write().await;
+        let offline: Vec<_> = stored
+            .values()
+            .filter(|app| app.state() == ApplicationState::Offline)
+            .cloned()
+            .collect();
+        *stored = applications
+            .into_iter()
+            .map(|application| (*application.id(), Arc::new(application)))
+            .collect();
+        for application in offline {
+            stored.entry(*application.id()).or_insert(application);
+        }
+    }
+
+    /// Returns the gRPC metadata sent to the application, kept in the secrets
+    pub async fn get_app_metadata(&self, uuid: Uuid) -> HashMap<String, String> {
+        self.database
+            .secrets_read()
+            .await
+            .metadata
+            .get(&uuid)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Keeps the gRPC metadata sent to the application in the secrets
+    pub async fn store_app_metadata(&self, uuid: Uuid, metadata: HashMap<String, String>) {
+        if metadata.is_empty() {
+            self.remove_app_metadata(uuid).await;
+        } else {
+            self.database
+                .secrets_write()
+                .await
+                .metadata
+                .insert(uuid, metadata);
+        }
+    }
+
+    async fn remove_app_metadata(&self, uuid: Uuid) {
+        // Avoids rewriting the secrets for applications without any
+        if self
+            .database
+            .secrets_read()
+            .await
+            .metadata
+            .contains_key(&uuid)
+        {
+            self.database.secrets_write().await.metadata.remove(&uuid);
+        }
+    }
+
+    pub async fn set_app_proxy_port(
+        &self,
+        uuid: Uuid,
+        proxy_port: Option<u16>,
+    ) -> Result<(), TraceError> {
+        let mut guard = self.database.applications_write().await;
+        let application = guard
+            .get_mut(&uuid)
+            .ok_or(TraceError::ApplicationNotFound(uuid))?;
+        application.writeable().set_proxy_port(proxy_port);
+
+        Ok(())
+    }
+
     pub async fn delete_app(&self, uuid: Uuid) {
         self.database.applications_write().await.remove(&uuid);
+        self.remove_app_metadata(uuid).await;
+        self.resources
+            .write()
+            .await
+            .retain(|_, resource| resource.app_id != uuid);
+        self.async_ops
+            .write()
+            .await
+            .retain(|_, async_op| async_op.app_id != uuid);
+        self.clocks.write().await.remove(&uuid);
+        self.removed_tasks.write().await.remove(&uuid);
+        self.connection_statuses.write().await.remove(&uuid);
+        self.connection_errors.write().await.remove(&uuid);
+        self.data_loss.write().await.remove(&uuid);
+        self.outputs.write().await.remove(&uuid);
+        self.history.write().await.remove_app(uuid);
+        let prefix = format!("{}.", uuid);
+        self.histograms
+            .write()
+            .await
+            .retain(|key, _| !key.starts_with(&prefix));
+        self.poll_spans
+            .write()
+            .await
+            .retain(|key, _| !key.starts_with(&prefix));
     }
 
     // endregion
@@ -112,25 +349,76 @@ impl State {
                 return;
             }
 
+            // A new process reuses the ids of the previous one
+            if self.reuses_task_ids(app_id, &task_update).await {
+                self.record_restart(app_id).await;
+            }
+
             // Saviing new tasks
-            for task in task_update.new_tasks {
-                if let Some(task) = map_to_domain_task(app_id, &task) {
+            for task in &task_update.new_tasks {
+                if let Some(task) = map_to_domain_task(app_id, task) {
                     info!("Received a new task for application with id {app_id}");
-                    self.database
+                    let created_at = created_at(&task_update, task.id);
+                    let other_task = self
+                        .database
+                        .tasks_read()
+                        .await
+                        .get(&task.id())
+                        .is_some_and(|stored| is_other_task(stored, &task, created_at));
+                    self.tasks_size
+                        .fetch_add(stored_size(&task), Ordering::Relaxed);
+                    let replaced = self
+                        .database
                         .tasks_write()
                         .await
                         .insert(task.id(), Arc::new(task));
+                    if let Some(replaced) = replaced {
+                        self.tasks_size
+                            .fetch_sub(stored_size(&replaced), Ordering::Relaxed);
+                        // Applications send their tasks again on every new
+                        // watch, only the tasks of a previous process are removed
+                        if other_task {
+                            self.poll_spans.write().await.remove(&replaced.id());
+                            self.removed_tasks
+                                .write()
+                                .await
+                                .entry(app_id)
+                                .or_default()
+                                .add(&replaced);
+                        }
+                    }
                 }
             }
 
-            // Saving dropped tasks
-            for (tid, updated_task) in task_update.stats_update {
-                if updated_task.dropped_at.is_some() {
-                    info!("A task was dropped for application {app_id}");
-                    self.database
-                        .tasks_write()
-                        .await
-                        .remove(&format!("{}.{}", app_id, tid));
+            // Saving dropped tasks and the latest stats
+            if !task_update.stats_update.is_empty() {
+                let mut tasks = self.database.tasks_write().await;
+                let mut poll_spans = self.poll_spans.write().await;
+                for (tid, updated_task) in task_update.stats_update {
+                    let key = format!("{}.{}", app_id, tid);
+                    if updated_task.dropped_at.is_some() {
+                        // Completed tasks are kept as history, with their polls,
+                        // until removed by the retention
+                        info!("A task was dropped for application {app_id}");
+                        self.histograms.write().await.remove(&key);
+                    }
+                    if let Some(task) = tasks.get_mut(&key) {
+                        let previous_poll_ended = task.stats.last_poll_ended;
+                        update_domain_task(task.writeable(), &updated_task);
+
+                        // Only the last poll since the previous update can be observed
+                        if let (Some(started), Some(ended)) =
+                            (task.stats.last_poll_started, task.stats.last_poll_ended)
+                        {
+                            if Some(ended) != previous_poll_ended && started <= ended {
+                                let spans = poll_spans.entry(key).or_default();
+                                if spans.len() == MAX_POLL_SPANS {
+                                    spans.pop_front();
+                                }
+                                spans.push_back(PollSpan { started, ended });
+                            }
+                        }
+                    }
                 }
             }
         } else {
@@ -138,9 +426,658 @@ impl State {
         }
     }
 
+    /// Whether new tasks have the id of another stored task (see `is_other_task`)
+    ///
+    /// Applications send all their tasks again once reconnected, with the
+    /// same ids as long as it is the same process.
+    async fn reuses_task_ids(&self, app_id: Uuid, task_update: &TaskUpdate) -> bool {
+        let tasks = self.database.tasks_read().await;
+        task_update
+            .new_tasks
+            .iter()
+            .filter_map(|task| map_to_domain_task(app_id, task))
+            .any(|task| {
+                tasks.get(&task.id()).is_some_and(|stored| {
+                    is_other_task(stored, &task, created_at(task_update, task.id))
+                })
+            })
+    }
+
+    /// Replaces all the tasks, eg: with the ones of the collector
+    ///
+    /// The tasks of the offline applications are kept, as their recordings
+    /// are imported by the windows
+    pub async fn replace_tasks(&self, tasks: Vec<Task>) {
+        let offline: HashSet<Uuid> = self
+            .database
+            .applications_read()
+            .await
+            .values()
+            .filter(|app| app.state() == ApplicationState::Offline)
+            .map(|app| *app.id())
+            .collect();
+
+        let mut stored = self.database.tasks_write().await;
+        stored.retain(|_, task| offline.contains(&task.app_id));
+        stored.extend(tasks.into_iter().map(|task| (task.id(), Arc::new(task))));
+        let size = stored.values().map(|task| stored_size(task)).sum();
+        self.tasks_size.store(size, Ordering::Relaxed);
+
+        // Tasks dropped meanwhile are not observed anymore
+        self.histograms
+            .write()
+            .await
+            .retain(|key, _| stored.contains_key(key));
+        self.poll_spans
+            .write()
+            .await
+            .retain(|key, _| stored.contains_key(key));
+    }
+
     pub async fn get_tasks(&self) -> Vec<Arc<Task>> {
         self.database.tasks_read().await.values().cloned().collect()
     }
 
+    /// Returns the stored tasks in constant time, for the readers going
+    /// through them without keeping them (eg: on every update)
+    pub async fn get_tasks_snapshot(&self) -> Tasks {
+        self.database.tasks_read().await
+    }
+
+    /// Returns the tasks which are not completed, the completed ones are
+    /// only kept as history
+    pub async fn get_live_tasks(&self) -> Vec<Arc<Task>> {
+        self.database
+            .tasks_read()
+            .await
+            .values()
+            .filter(|task| task.stats.dropped_at.is_none())
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the latest polls observed for every task of the application
+    pub async fn get_poll_spans(&self, app_id: Uuid) -> HashMap<u64, Vec<PollSpan>> {
+        let prefix = format!("{}.", app_id);
+        self.poll_spans
+            .read()
+            .await
+            .iter()
+            .filter_map(|(key, spans)| {
+                let task_id = key.strip_prefix(&prefix)?.parse().ok()?;
+                Some((task_id, spans.iter().copied().collect()))
+            })
+            .collect()
+    }
+
+    pub async fn handle_task_details(&self, app_id: Uuid, details: TaskDetails) {
+        let Some(task_id) = details.task_id else {
+            return;
+        };
+        if !self.is_app_enabled(app_id).await {
+            return;
+        }
+
+        self.histograms.write().await.insert(
+            format!("{}.{}", app_id, task_id.id),
+            Arc::new(map_to_domain_histograms(&details)),
+        );
+    }
+
+    pub async fn get_task_histograms(
+        &self,
+        app_id: Uuid,
+        task_id: u64,
+    ) -> Option<Arc<TaskHistograms>> {
+        self.histograms
+            .read()
+            .await
+            .get(&format!("{}.{}", app_id, task_id))
+            .cloned()
+    }
+
+    /// Returns the latest histograms of the watched tasks of the application
+    pub async fn get_app_histograms(&self, app_id: Uuid) -> HashMap<u64, Arc<TaskHistograms>> {
+        let prefix = format!("{}.", app_id);
+        self.histograms
+            .read()
+            .await
+            .iter()
+            .filter_map(|(key, histograms)| {
+                let task_id = key.strip_prefix(&prefix)?.parse().ok()?;
+                Some((task_id, histograms.clone()))
+            })
+            .collect()
+    }
+
+    /// Merges the histograms of the given tasks, skipping the ones
+    /// whose details were not received yet
+    pub async fn get_merged_histograms(&self, app_id: Uuid, task_ids: &[u64]) -> TaskHistograms {
+        let histograms = self.histograms.read().await;
+        let task_histograms: Vec<&TaskHistograms> = task_ids
+            .iter()
+            .filter_map(|task_id| histograms.get(&format!("{}.{}", app_id, task_id)))
+            .map(|histograms| histograms.as_ref())
+            .collect();
+
+        TaskHistograms::merge(task_histograms.iter().copied())
+    }
+
     // endregion
+
+    // region RESOURCES
+
+    pub async fn handle_resource_update(
+        &self,
+        app_id: Uuid,
+        resource_update: ResourceUpdate,
+        now: Option<SystemTime>,
+    ) {
+        if !self.is_app_enabled(app_id).await {
+            return;
+        }
+
+        let mut resources = self.resources.write().await;
+
+        // Saving new resources
+        for resource in resource_update.new_resources {
+            if let Some(resource) = map_to_domain_resource(app_id, &resource) {
+                resources.insert(resource.id(), Arc::new(resource));
+            }
+        }
+
+        // Updating stats and removing dropped resources
+        for (rid, stats) in resource_update.stats_update {
+            let key = format!("{}.{}", app_id, rid);
+            if stats.dropped_at.is_some() {
+                resources.remove(&key);
+            } else if let Some(resource) = resources.get_mut(&key) {
+                update_domain_resource(resource.writeable(), &stats);
+            }
+        }
+
+        // Poll ops tell which task is waiting for, or was granted, a resource
+        let mut async_ops = self.async_ops.write().await;
+        for poll_op in resource_update.new_poll_ops {
+            let (Some(task_id), Some(async_op_id)) = (poll_op.task_id, poll_op.async_op_id) else {
+                continue;
+            };
+
+            if let Some(async_op) = async_ops.get_mut(&format!("{}.{}", app_id, async_op_id.id)) {
+                let async_op = async_op.writeable();
+                async_op.task_id = Some(task_id.id);
+                if poll_op.is_ready {
+                    async_op.pending_since = None;
+                } else if async_op.pending_since.is_none() {
+                    async_op.pending_since = now;
+                }
+            }
+
+            if poll_op.is_ready {
+                if let Some(resource_id) = poll_op.resource_id {
+                    if let Some(resource) =
+                        resources.get_mut(&format!("{}.{}", app_id, resource_id.id))
+                    {
+                        resource.writeable().holder_task_id = Some(task_id.id);
+                    }
+                }
+            }
+        }
+    }
+
+    pub async fn get_resources(&self) -> Vec<Arc<Resource>> {
+        self.resources.read().await.values().cloned().collect()
+    }
+
+    // endregion
+
+    // region ASYNC OPS
+
+    pub async fn handle_async_op_update(&self, app_id: Uuid, async_op_update: AsyncOpUpdate) {
+        if !self.is_app_enabled(app_id).await {
+            return;
+        }
+
+        let mut async_ops = self.async_ops.write().await;
+
+        // Saving new async ops
+        for async_op in async_op_update.new_async_ops {
+            if let Some(async_op) = map_to_domain_async_op(app_id, &async_op) {
+                async_ops.insert(async_op.id(), Arc::new(async_op));
+            }
+        }
+
+        // Updating stats and removing dropped async ops
+        for (oid, stats) in async_op_update.stats_update {
+            let key = format!("{}.{}", app_id, oid);
+            if stats.dropped_at.is_some() {
+                async_ops.remove(&key);
+            } else if let Some(async_op) = async_ops.get_mut(&key) {
+                update_domain_async_op(async_op.writeable(), &stats);
+            }
+        }
+    }
+
+    pub async fn get_async_ops(&self) -> Vec<Arc<AsyncOp>> {
+        self.async_ops.read().await.values().cloned().collect()
+    }
+
+    // endregion
+
+    // region UPDATES
+
+    /// Synchronizes the clock of the application with the time of an update
+    /// (in the application's clock), received at the local time `local_now`
+    ///
+    /// The update may be processed later, the time it waited in the queue
+    /// must not be taken for a clock offset
+    pub async fn sync_clock(&self, app_id: Uuid, now: SystemTime, local_now: SystemTime) {
+        let recorded = self.get_app_state(app_id).await == Some(ApplicationState::Offline);
+        let mut clocks = self.clocks.write().await;
+        match clocks.get_mut(&app_id) {
+            Some(clock) => clock.sync(now, local_now),
+            None => {
+                let clock = if recorded {
+                    Clock::recorded(now)
+                } else {
+                    Clock::new(now, local_now)
+                };
+                clocks.insert(app_id, clock);
+            }
+        }
+    }
+
+    /// Stops the clock of the application until its next update, as it is not
+    /// connected anymore
+    pub async fn pause_clock(&self, app_id: Uuid) {
+        if let Some(clock) = self.clocks.write().await.get_mut(&app_id) {
+            clock.pause();
+        }
+    }
+
+    async fn record_restart(&self, app_id: Uuid) {
+        self.history.write().await.restarted(app_id);
+        if let Some(clock) = self.clocks.write().await.get_mut(&app_id) {
+            clock.restarted();
+            warn!(
+                "Application {app_id} restarted ({} times so far), its previous statistics may be mixed with the new ones",
+                clock.restarts()
+            );
+        }
+    }
+
+    /// Returns the time of the last update of the application (in the application's clock)
+    pub async fn get_last_update(&self, app_id: Uuid) -> Option<SystemTime> {
+        let clocks = self.clocks.read().await;
+        clocks.get(&app_id).map(Clock::last_update)
+    }
+
+    /// Estimates the current time of the application (in the application's clock),
+    /// to compute ages which keep increasing while no update is received
+    pub async fn get_app_now(&self, app_id: Uuid) -> Option<SystemTime> {
+        let clocks = self.clocks.read().await;
+        clocks
+            .get(&app_id)
+            .map(|clock| clock.remote_now(SystemTime::now()))
+    }
+
+    /// Converts a timestamp of the application to the local clock
+    pub async fn to_local_time(&self, app_id: Uuid, time: SystemTime) -> Option<SystemTime> {
+        let clocks = self.clocks.read().await;
+        clocks.get(&app_id).map(|clock| clock.to_local(time))
+    }
+
+    /// Accounts the events the application reported as dropped in an update
+    pub async fn record_dropped_events(
+        &self,
+        app_id: Uuid,
+        now: SystemTime,
+        dropped: DroppedEvents,
+    ) {
+        let mut data_loss = self.data_loss.write().await;
+        // Applications which never dropped events are not tracked
+        if dropped.total() > 0 || data_loss.contains_key(&app_id) {
+            data_loss.entry(app_id).or_default().record(now, dropped);
+        }
+    }
+
+    /// Counts the dropped events of the application from zero, once connected again
+    pub async fn reset_dropped_events(&self, app_id: Uuid) {
+        if let Some(data_loss) = self.data_loss.write().await.get_mut(&app_id) {
+            data_loss.reconnected();
+        }
+    }
+
+    pub async fn get_data_loss(&self) -> HashMap<Uuid, DataLoss> {
+        self.data_loss.read().await.clone()
+    }
+
+    // endregion
+
+    // region CONNECTIONS
+
+    pub async fn set_connection_status(&self, app_id: Uuid, status: ConnectionStatus) {
+        self.connection_statuses
+            .write()
+            .await
+            .insert(app_id, status);
+    }
+
+    pub async fn get_connection_statuses(&self) -> HashMap<Uuid, ConnectionStatus> {
+        self.connection_statuses.read().await.clone()
+    }
+
+    pub async fn set_connection_error(&self, app_id: Uuid, error: Option<ErrorReport>) {
+        let mut connection_errors = self.connection_errors.write().await;
+        match error {
+            Some(error) => connection_errors.insert(app_id, error),
+            None => connection_errors.remove(&app_id),
+        };
+    }
+
+    pub async fn get_connection_errors(&self) -> HashMap<Uuid, ErrorReport> {
+        self.connection_errors.read().await.clone()
+    }
+
+    // endregion
+
+    // region HISTORY
+
+    /// Records the current metrics of the application and its tasks, at the
+    /// time of an update (in the application's clock)
+    ///
+    /// Samples are timestamped in the local clock, so that the history of
+    /// all the applications can be compared
+    pub async fn record_metrics(&self, app_id: Uuid, now: SystemTime, task_ids: &[u64]) {
+        if !self.is_app_enabled(app_id).await {
+            return;
+        }
+
+        let at = self.to_local_time(app_id, now).await.unwrap_or(now);
+        let tasks = self.get_tasks_snapshot().await;
+        self.history
+            .write()
+            .await
+            .record(app_id, now, at, &tasks, task_ids);
+    }
+
+    pub async fn get_metrics_history(
+        &self,
+        app_id: Uuid,
+        task_id: Option<u64>,
+        from: Option<u64>,
+        to: Option<u64>,
+    ) -> Vec<MetricsSample> {
+        self.history.read().await.query(app_id, task_id, from, to)
+    }
+
+    /// Returns the mean rates of an application, or of one of its tasks,
+    /// since `from` (milliseconds since UNIX epoch)
+    pub async fn get_windowed_rates(
+        &self,
+        app_id: Uuid,
+        task_id: Option<u64>,
+        from: u64,
+    ) -> Option<Metrics> {
+        self.history.read().await.window(app_id, task_id, from)
+    }
+
+    /// Returns the mean rates of the tasks sampled since `from`, by task key
+    pub async fn get_task_windows(&self, from: u64) -> HashMap<String, Metrics> {
+        self.history.read().await.task_windows(from)
+    }
+
+    // endregion
+
+    // region RETENTION
+
+    /// Removes the completed tasks exceeding the retention limits, oldest first
+    ///
+    /// Returns the number of removed tasks
+    pub async fn compact_tasks(&self, retention: &RetentionSettings) -> usize {
+        let local_now = SystemTime::now();
+        let tasks = self.database.tasks_read().await;
+
+        // Completed tasks, with the local time they completed at and their size
+        let mut completed = Vec::new();
+        let mut total_size = self.tasks_size.load(Ordering::Relaxed);
+        {
+            let clocks = self.clocks.read().await;
+            for (key, task) in &tasks {
+                if let Some(dropped_at) = task.stats.dropped_at {
+                    let completed_at = clocks
+                        .get(&task.app_id)
+                        .map_or(dropped_at, |clock| clock.to_local(dropped_at));
+                    completed.push((key, task.app_id, completed_at, stored_size(task)));
+                }
+            }
+        }
+        // Latest first
+        completed.sort_by_key(|&(_, _, completed_at, _)| std::cmp::Reverse(completed_at));
+
+        let mut removed = HashSet::new();
+        let mut kept_by_app: HashMap<Uuid, usize> = HashMap::new();
+        for &(key, app_id, completed_at, size) in &completed {
+            let expired = retention.max_age.is_some_and(|max_age| {
+                local_now
+                    .duration_since(completed_at)
+                    .is_ok_and(|age| age.as_secs() > max_age)
+            });
+            let kept = kept_by_app.entry(app_id).or_default();
+            if expired || retention.max_tasks_per_app.is_some_and(|max| *kept >= max) {
+                removed.insert(key.clone());
+                total_size = total_size.saturating_sub(size);
+            } else {
+                *kept += 1;
+            }
+        }
+        if let Some(max_total_size) = retention.max_total_size {
+            for &(key, _, _, size) in completed.iter().rev() {
+                if total_size <= max_total_size {
+                    break;
+                }
+                if removed.insert(key.clone()) {
+                    total_size = total_size.saturating_sub(size);
+                }
+            }
+        }
+        drop(tasks);
+
+        // Publishes the tasks once, whatever the number of removed tasks
+        if !removed.is_empty() {
+            self.remove_tasks(|key, _| removed.contains(key)).await;
+        }
+        removed.len()
+    }
+
+    /// Removes the completed tasks and the metrics history of the application
+    pub async fn purge_app_history(&self, app_id: Uuid) {
+        self.remove_tasks(|_, task| task.app_id == app_id && task.stats.dropped_at.is_some())
+            .await;
+        self.history.write().await.remove_app(app_id);
+    }
+
+    /// Removes the stored tasks matching `condition`, keeping their size up to date
+    async fn remove_tasks(&self, condition: impl Fn(&String, &Task) -> bool) {
+        let mut tasks = self.database.tasks_write().await;
+        let mut removed_size = 0;
+        let mut removed_tasks = self.removed_tasks.write().await;
+        let mut poll_spans = self.poll_spans.write().await;
+        tasks.retain(|key, task| {
+            let remove = condition(key, task);
+            if remove {
+                removed_size += stored_size(task);
+                removed_tasks.entry(task.app_id).or_default().add(task);
+                poll_spans.remove(key);
+            }
+            !remove
+        });
+        // The size is estimated, it must not wrap if the estimates drifted
+        let _ = self
+            .tasks_size
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |size| {
+                Some(size.saturating_sub(removed_size))
+            });
+    }
+
+    /// Returns the polls of the tasks removed from every application
+    pub async fn get_removed_tasks(&self) -> HashMap<Uuid, RemovedTasks> {
+        self.removed_tasks.read().await.clone()
+    }
+
+    // endregion
+
+    // region SETTINGS
+
+    pub async fn get_settings(&self) -> Settings {
+        self.database.settings_read().await
+    }
+
+    pub async fn store_settings(&self, settings: Settings) {
+        *self.database.settings_write().await = settings;
+    }
+
+    // endregion
+
+    // region UTILS
+
+    async fn get_app_state(&self, app_id: Uuid) -> Option<ApplicationState> {
+        self.database
+            .applications_read()
+            .await
+            .get(&app_id)
+            .map(|app| app.state())
+    }
+
+    async fn is_app_enabled(&self, app_id: Uuid) -> bool {
+        match self.database.applications_read().await.get(&app_id) {
+            // Offline applications receive the updates of their recording
+            Some(app) => app.state() != ApplicationState::Disabled,
+            None => {
+                warn!("Received an update for an app that is not registered");
+                false
+            }
+        }
+    }
+
+    // endregion
+}
+
+/// Creation time of a task, sent with its stats
+fn created_at(task_update: &TaskUpdate, task_id: u64) -> Option<SystemTime> {
+    task_update
+        .stats_update
+        .get(&task_id)
+        .and_then(|stats| map_timestamp(stats.created_at.as_ref()))
+}
+
+/// Whether a task received with the id of a stored task is another task, ie:
+/// the stored one completed, or was created at another time or location
+fn is_other_task(stored: &Task, task: &Task, created_at: Option<SystemTime>) -> bool {
+    stored.stats.dropped_at.is_some()
+        || (created_at.is_some() && created_at != stored.stats.created_at)
+        || stored.location != task.location
+}
+
+/// Estimated size of a task in the tasks file, without serializing it
+fn stored_size(task: &Task) -> u64 {
+    let text = |value: &Option<String>| value.as_ref().map_or(0, |value| value.len() as u64);
+    TASK_FIXED_SIZE + text(&task.name) + text(&task.kind) + text(&task.location)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use console_api::{
+        tasks::{Stats, Task as ConsoleTask},
+        Id, Location, PollStats,
+    };
+    use prost_types::Timestamp;
+    use tokio::sync::mpsc;
+
+    const TASK_ID: u64 = 1;
+
+    async fn state_with_app() -> (State, Uuid) {
+        let state = State::in_memory();
+        let application =
+            Application::new("app".to_owned(), "http://localhost:6669".parse().unwrap());
+        let app_id = *application.id();
+        state.store_app(application).await;
+        let (commands, _) = mpsc::channel(1);
+        state
+            .enable_app(app_id, Connection { commands })
+            .await
+            .unwrap();
+        (state, app_id)
+    }
+
+    /// Update holding the whole task, as sent on every new watch
+    fn task_update(created_at: i64, polls: u64) -> TaskUpdate {
+        TaskUpdate {
+            new_tasks: vec![ConsoleTask {
+                id: Some(Id { id: TASK_ID }),
+                location: Some(Location {
+                    file: Some("src/main.rs".to_owned()),
+                    line: Some(10),
+                    column: Some(5),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }],
+            stats_update: HashMap::from([(
+                TASK_ID,
+                Stats {
+                    created_at: Some(Timestamp {
+                        seconds: created_at,
+                        nanos: 0,
+                    }),
+                    poll_stats: Some(PollStats {
+                        polls,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            )]),
+            dropped_events: 0,
+        }
+    }
+
+    async fn removed_polls(state: &State, app_id: Uuid) -> u64 {
+        state
+            .get_removed_tasks()
+            .await
+            .get(&app_id)
+            .map_or(0, |removed| removed.polls)
+    }
+
+    async fn stored_polls(state: &State) -> u64 {
+        state
+            .get_tasks()
+            .await
+            .iter()
+            .map(|task| task.stats.polls)
+            .sum()
+    }
+
+    #[tokio::test]
+    async fn resent_task_is_not_counted_as_removed() {
+        let (state, app_id) = state_with_app().await;
+
+        state.handle_task_update(app_id, task_update(100, 5)).await;
+        state.handle_task_update(app_id, task_update(100, 5)).await;
+
+        assert_eq!(removed_polls(&state, app_id).await, 0);
+        assert_eq!(stored_polls(&state).await, 5);
+    }
+
+    #[tokio::test]
+    async fn task_of_restarted_process_is_counted_as_removed() {
+        let (state, app_id) = state_with_app().await;
+
+        state.handle_task_update(app_id, task_update(100, 5)).await;
+        state.handle_task_update(app_id, task_update(200, 2)).await;
+
+        assert_eq!(removed_polls(&state, app_id).await, 5);
+        assert_eq!(stored_polls(&state).await, 2);
+    }
 }
@@ -1,4 +1,5 @@
 import { Application } from "@/types/applications";
+import { TraceError } from "@/types/errors";
 import { invoke } from "@tauri-apps/api/core";
 import { defineStore } from "pinia";
 import { computed, Ref, ref } from "vue";
@@ -19,7 +20,7 @@ export const useApplicationStore = defineStore('applications', () => {
                 });
             }
         ).catch(
-            (error) => console.log("Failed to send add application command: " + error)
+            (error) => console.log("Failed to send add application command: " + (error as TraceError).message)
         );
     }
 
@@ -29,7 +30,7 @@ export const useApplicationStore = defineStore('applications', () => {
                 applications.value = applications.value.filter(item => item.id !== appID);
             }
         ).catch(
-            (error) => console.log("Failed to send delete application command: " + error)
+            (error) => console.log("Failed to send delete application command: " + (error as TraceError).message)
         );
     }
 
@@ -1,11 +1,56 @@
+import { TraceError } from "@/types/errors";
+import { Duration, SystemTime } from "@/types/tasks";
+
 export type Application = {
   id: string;
   title: string;
   url: string;
   state: string;
+  proxy_port?: number;
+  exit_code?: number;
+  pre_connect?: PreConnectHook;
 
   startTime?: string,
   pid?: number,
   cpuUsage?: number,
   memoryUsage?: number,
 }
+
+export type PreConnectHook = {
+  program: string;
+  args: string[];
+  ready_timeout: number;
+};
+
+/** Why the applications could not be connected to, by application id */
+export type ConnectionErrors = Record<string, TraceError>;
+
+export type ExportFormat = 'Json' | 'TasksCsv' | 'ResourcesCsv' | 'ChromeTrace';
+
+export type OutputLine = {
+  stream: 'Stdout' | 'Stderr';
+  line: string;
+  at: SystemTime;
+};
+
+export type OutputEvent = OutputLine & {
+  app_id: string;
+};
+
+export type DiscoveredEndpoint = {
+  url: string;
+  port: number;
+  pid?: number;
+  command_line?: string;
+  registered: boolean;
+};
+
+/** How the events of a connected application are ingested */
+export type IngestionStats = {
+  queued: number;
+  received: number;
+  coalesced: number;
+  discarded: number;
+  lag: Duration;
+  max_lag: Duration;
+};
@@ -0,0 +1,25 @@
+import { SystemTime } from "@/types/tasks";
+
+/** Number of events an application could not record, as its event buffer was full */
+export type DroppedEvents = {
+    tasks: number;
+    resources: number;
+    async_ops: number;
+};
+
+/** Successive updates of an application reporting dropped events */
+export type DropBurst = {
+    started_at: SystemTime;
+    ended_at: SystemTime;
+    dropped: DroppedEvents;
+};
+
+/** Data an application could not send, its statistics may be wrong while incomplete */
+export type DataLoss = {
+    dropped: DroppedEvents;
+    incomplete: boolean;
+    bursts: DropBurst[];
+};
+
+/** Payload of the `warning:data_loss` event, by application id */
+export type DataLossWarning = Record<string, DataLoss>;
@@ -0,0 +1,10 @@
+export type ErrorCategory =
+    'network' | 'protocol' | 'storage' | 'validation' | 'process' | 'internal';
+
+/** Error returned by the commands, `code` is stable while `message` may change */
+export type TraceError = {
+    code: string;
+    category: ErrorCategory;
+    message: string;
+    details: Record<string, unknown> | null;
+};
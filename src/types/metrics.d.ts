@@ -0,0 +1,56 @@
+import { Duration, Task } from "@/types/tasks";
+
+export type Metrics = {
+    live_tasks: number;
+    polls_per_sec: number;
+    busy_ratio: number;
+    wakes_per_sec: number;
+    scheduled_ratio: number;
+    // seconds
+    scheduling_latency: number;
+};
+
+export type MetricsSample = Metrics & {
+    // milliseconds since UNIX epoch, in the local clock
+    timestamp: number;
+};
+
+export type ActivityOrder = "busy" | "polls" | "wakes";
+
+/** Rates of a live task over the latest seconds */
+export type TaskActivity = Metrics & {
+    task: Task;
+};
+
+export type SaturationLevel = "normal" | "elevated" | "saturated";
+
+/** Whether the runtime keeps up with the woken tasks, over the recent samples */
+export type Saturation = {
+    level: SaturationLevel;
+    recent_latency: Duration;
+    waiting_tasks: number;
+    busy_workers: number;
+};
+
+export type LatencyPercentiles = {
+    p50: Duration;
+    p90: Duration;
+    p99: Duration;
+    max: Duration;
+};
+
+export type TaskScheduling = {
+    task_id: number;
+    name?: string;
+    location?: string;
+    mean_latency: Duration;
+    /** Only for the watched tasks */
+    percentiles?: LatencyPercentiles;
+};
+
+export type SchedulingReport = {
+    app_id: string;
+    saturation: Saturation;
+    percentiles?: LatencyPercentiles;
+    tasks: TaskScheduling[];
+};
@@ -0,0 +1,53 @@
+export type MetricsEndpointSettings = {
+  enabled: boolean;
+  port: number;
+};
+
+export type ApiSettings = {
+  enabled: boolean;
+  port: number;
+  token: string;
+  /** Accept the applications added through the API with a pre-connect command */
+  allow_pre_connect: boolean;
+};
+
+export type PortRange = {
+  start: number;
+  end: number;
+};
+
+export type DiscoverySettings = {
+  port_ranges: PortRange[];
+};
+
+/** Limits of the completed tasks kept, unset limits are not applied */
+export type RetentionSettings = {
+  /** Seconds */
+  max_age?: number;
+  max_tasks_per_app?: number;
+  /** Bytes */
+  max_total_size?: number;
+};
+
+/** Thresholds of the task leak detection */
+export type LeakDetectionSettings = {
+  /** Seconds */
+  idle_threshold: number;
+  /** Seconds */
+  window: number;
+  min_growth: number;
+};
+
+export type BlockingDetectionSettings = {
+  /** Milliseconds */
+  poll_budget: number;
+};
+
+export type Settings = {
+  metrics_endpoint: MetricsEndpointSettings;
+  api: ApiSettings;
+  discovery: DiscoverySettings;
+  retention: RetentionSettings;
+  leak_detection: LeakDetectionSettings;
+  blocking_detection: BlockingDetectionSettings;
+};
@@ -0,0 +1,39 @@
+import { Duration } from "@/types/tasks";
+
+/** Attribute the tasks are grouped by, names matching a pattern (eg: `worker-*`) are grouped under it */
+export type GroupBy =
+    | { by: "location" }
+    | { by: "kind" }
+    | { by: "name"; patterns?: string[] }
+    | { by: "field"; field: string };
+
+export type TaskGrouping = GroupBy & {
+    app_id?: string;
+};
+
+export type DurationSummary = {
+    total: Duration;
+    p50: Duration;
+    p90: Duration;
+    p99: Duration;
+    max: Duration;
+};
+
+/** Change of a group since the grouping was previously computed */
+export type GroupTrend = {
+    period: Duration;
+    live_change: number;
+    spawned_per_sec: number;
+    busy_ratio: number;
+};
+
+/** Payload of the `update:task_groups` event is a list of groups, largest first */
+export type TaskGroup = {
+    key?: string;
+    count: number;
+    live: number;
+    completed: number;
+    busy: DurationSummary;
+    idle: DurationSummary;
+    trend?: GroupTrend;
+};
@@ -1,7 +1,75 @@
+export type SystemTime = {
+    secs_since_epoch: number;
+    nanos_since_epoch: number;
+};
+
+export type Duration = {
+    secs: number;
+    nanos: number;
+};
+
+export type TaskStats = {
+    created_at?: SystemTime;
+    dropped_at?: SystemTime;
+    wakes: number;
+    waker_clones: number;
+    waker_drops: number;
+    self_wakes: number;
+    last_wake?: SystemTime;
+    polls: number;
+    first_poll?: SystemTime;
+    last_poll_started?: SystemTime;
+    last_poll_ended?: SystemTime;
+    busy_time: Duration;
+    scheduled_time: Duration;
+};
+
 export type Task = {
     app_id: string,
     id: number;
     tid?: number;
     name?: string;
     kind: string;
+    location?: string;
+    stats: TaskStats;
+};
+
+export type HistogramBucket = {
+    upper_bound: Duration;
+    count: number;
+};
+
+export type DurationHistogram = {
+    count: number;
+    min: Duration;
+    p50: Duration;
+    p90: Duration;
+    p99: Duration;
+    max: Duration;
+    high_outliers: number;
+    highest_outlier?: Duration;
+    buckets: HistogramBucket[];
+};
+
+export type TaskHistograms = {
+    poll_times?: DurationHistogram;
+    scheduled_times?: DurationHistogram;
+};
+
+export type SortKey = {
+    field: string;
+    descending?: boolean;
+};
+
+export type TaskQuery = {
+    // eg: `busy > 10ms and polls < 5`
+    filter?: string;
+    sort?: SortKey[];
+    offset?: number;
+    limit?: number;
+};
+
+export type TaskPage = {
+    total: number;
+    tasks: Task[];
 };
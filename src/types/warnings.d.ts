@@ -0,0 +1,56 @@
+import { Duration, SystemTime } from "@/types/tasks";
+
+export type WaitingTask = {
+    task_id: number;
+    name?: string;
+    location?: string;
+    async_op_id: number;
+    async_op: string;
+    resource_id: number;
+    resource: string;
+    resource_location?: string;
+    holder_task_id?: number;
+    waiting_for: Duration;
+};
+
+export type DeadlockReport = {
+    app_id: string;
+    kind: 'Cycle' | 'LongWait';
+    tasks: WaitingTask[];
+};
+
+/** Payload of the `warning:task_leak` event is a list of reports */
+export type LeakReport = {
+    app_id: string;
+    kind: 'Growth' | 'LongIdle';
+    location: string;
+    live_tasks: number;
+    growth_per_minute: number;
+    idle_tasks: number;
+    task_ids: number[];
+};
+
+/** Poll exceeding the budget, the payload of the `warning:blocking` event is a list of them */
+export type BlockingIncident = {
+    app_id: string;
+    task_id: number;
+    name?: string;
+    location?: string;
+    started_at: SystemTime;
+    duration: Duration;
+    ongoing: boolean;
+};
+
+export type BlockingOffender = {
+    task_id: number;
+    name?: string;
+    location?: string;
+    polls_over_budget: number;
+    longest_poll: Duration;
+    blocked_time: Duration;
+};
+
+export type BlockingReport = {
+    incidents: BlockingIncident[];
+    worst_offenders: BlockingOffender[];
+};